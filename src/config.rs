@@ -0,0 +1,408 @@
+// --- Configuration ---
+// The config file format, and loading and saving it.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+// A configured command: either a plain command string or a table with options
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(untagged)]
+pub enum CommandConfig {
+    Plain(String),
+    Detailed(CommandTable),
+}
+
+// A command with per-command options, written inline in `commands` or as a `[[command]]` table
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CommandTable {
+    pub cmd: String,
+    #[serde(default)]
+    pub name: Option<String>, // Stable identifier for the command
+    #[serde(default)]
+    pub label: Option<String>, // Shown as the row title instead of the command line
+    #[serde(default)]
+    pub icon: Option<String>, // Icon name shown in front of the row
+    #[serde(default)]
+    pub shell: bool, // Run through `/bin/sh -c` so pipes, redirections and $VARS work
+    #[serde(default = "default_true")]
+    pub on_change: bool, // Set to false to only run this command on Enter
+    #[serde(default)]
+    pub timeout_secs: Option<u64>, // Overrides the global command timeout
+    #[serde(default)]
+    pub env: BTreeMap<String, String>, // Extra environment variables for the command
+    #[serde(default)]
+    pub cwd: Option<PathBuf>, // Directory to run the command in
+}
+
+impl CommandConfig {
+    // The command line as written in the config
+    pub fn cmd(&self) -> &str {
+        match self {
+            CommandConfig::Plain(cmd) => cmd,
+            CommandConfig::Detailed(table) => &table.cmd,
+        }
+    }
+
+    // The options table, if the command has one
+    fn table(&self) -> Option<&CommandTable> {
+        match self {
+            CommandConfig::Plain(_) => None,
+            CommandConfig::Detailed(table) => Some(table),
+        }
+    }
+
+    // What to call the command in the UI: its label, then its name, then the command line
+    pub fn label(&self) -> &str {
+        self.table()
+            .and_then(|table| table.label.as_deref().or(table.name.as_deref()))
+            .unwrap_or_else(|| self.cmd())
+    }
+
+    // Icon name to show next to the command's output, if any
+    pub fn icon(&self) -> Option<&str> {
+        self.table().and_then(|table| table.icon.as_deref())
+    }
+
+    // Whether the command should be run through the shell
+    pub fn shell(&self) -> bool {
+        self.table().is_some_and(|table| table.shell)
+    }
+
+    // Whether the command should run automatically as the input changes
+    pub fn on_change(&self) -> bool {
+        self.table().is_none_or(|table| table.on_change)
+    }
+
+    // The command's own timeout, if it overrides the global one
+    pub fn timeout_secs(&self) -> Option<u64> {
+        self.table().and_then(|table| table.timeout_secs)
+    }
+
+    // Extra environment variables to run the command with
+    pub fn env(&self) -> Option<&BTreeMap<String, String>> {
+        self.table().map(|table| &table.env)
+    }
+
+    // Directory to run the command in, if not the current one
+    pub fn cwd(&self) -> Option<&PathBuf> {
+        self.table().and_then(|table| table.cwd.as_ref())
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Config {
+    pub run_commands_on_change: bool,
+    #[serde(default)]
+    pub run_on_change_debounce_ms: u64, // Wait for typing to pause this long before running
+    #[serde(default)]
+    pub run_on_change_min_length: usize, // Don't run on change until the input is at least this long
+    #[serde(default)]
+    pub timeout_secs: Option<u64>, // Kill commands that run longer than this
+    #[serde(default)]
+    pub commands: Vec<CommandConfig>,
+    // `[[command]]` tables; these are appended to `commands` once the config is loaded
+    #[serde(default, rename = "command", skip_serializing_if = "Vec::is_empty")]
+    pub command_tables: Vec<CommandTable>,
+    pub title: String, // Added title field to Config
+    #[serde(default)]
+    pub stay_resident: bool, // Closing the window hides it instead of quitting
+    #[serde(default)]
+    pub clear_input_on_hide: bool, // Start over with an empty input each time the window is hidden
+    #[serde(default)]
+    pub clear_input_on_dismiss: bool, // Start over with an empty input after copying a result and closing
+    #[serde(default)]
+    pub hide_on_escape: bool, // Close the window with Escape
+    #[serde(default)]
+    pub hide_on_focus_loss: bool, // Close the window when another window is focused
+    #[serde(default)]
+    pub multiline_input: bool, // Type into a text area that keeps newlines; Ctrl+Enter runs
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keybindings: BTreeMap<String, String>, // Shortcut -> action, on top of the default shortcuts
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>, // Named command sets to switch between
+}
+
+// A named set of commands that can be switched to at runtime, written as `[profiles.<name>]`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Profile {
+    #[serde(default)]
+    pub title: Option<String>, // Defaults to the profile's name
+    #[serde(default)]
+    pub run_commands_on_change: Option<bool>, // Defaults to the top-level setting
+    #[serde(default)]
+    pub multiline_input: Option<bool>, // Defaults to the top-level setting
+    #[serde(default)]
+    pub commands: Vec<CommandConfig>,
+    #[serde(default, rename = "command", skip_serializing_if = "Vec::is_empty")]
+    pub command_tables: Vec<CommandTable>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            run_commands_on_change: false,
+            run_on_change_debounce_ms: 0,
+            run_on_change_min_length: 0,
+            timeout_secs: None,
+            commands: vec![
+                CommandConfig::Plain("cat".to_string()),
+                CommandConfig::Plain("wc".to_string()),
+            ],
+            command_tables: Vec::new(),
+            title: "Putput".to_string(), // Default title
+            stay_resident: false,
+            clear_input_on_hide: false,
+            clear_input_on_dismiss: false,
+            hide_on_escape: false,
+            hide_on_focus_loss: false,
+            multiline_input: false,
+            keybindings: BTreeMap::new(),
+            profiles: BTreeMap::new(),
+        }
+    }
+}
+
+impl Config {
+    // Folds `[[command]]` tables into `commands`, so commands can be addressed by index
+    fn merge_command_tables(mut self) -> Self {
+        let tables = std::mem::take(&mut self.command_tables);
+        self.commands
+            .extend(tables.into_iter().map(CommandConfig::Detailed));
+        for profile in self.profiles.values_mut() {
+            let tables = std::mem::take(&mut profile.command_tables);
+            profile
+                .commands
+                .extend(tables.into_iter().map(CommandConfig::Detailed));
+        }
+        self
+    }
+
+    // The config to run with while a profile is selected; None selects the top-level commands
+    // Settings a profile doesn't override are taken from the top level
+    pub fn for_profile(&self, name: Option<&str>) -> Config {
+        let (Some(name), Some(profile)) = (name, name.and_then(|name| self.profiles.get(name)))
+        else {
+            return self.clone();
+        };
+        Config {
+            title: profile.title.clone().unwrap_or_else(|| name.to_string()),
+            run_commands_on_change: profile
+                .run_commands_on_change
+                .unwrap_or(self.run_commands_on_change),
+            multiline_input: profile.multiline_input.unwrap_or(self.multiline_input),
+            // A profile that lists neither `commands` nor `[[command]]` tables keeps the top-level ones
+            commands: if profile.commands.is_empty() {
+                self.commands.clone()
+            } else {
+                profile.commands.clone()
+            },
+            ..self.clone()
+        }
+    }
+}
+
+// --- Config Loading and Saving ---
+
+// Why the config file couldn't be used
+#[derive(Debug)]
+pub struct ConfigError {
+    pub path: PathBuf,
+    pub message: String, // Includes the line and column for parse errors
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+// Loads the configuration file, creating a default one if it doesn't exist yet
+// A broken config file is reported and left untouched, so no hand-written commands are lost
+pub fn load_config(config_path: &Path) -> Result<Config, ConfigError> {
+    // Attempt to read the config file
+    match fs::read_to_string(config_path) {
+        Ok(content) => match toml::from_str::<Config>(&content) {
+            Ok(config) => {
+                eprintln!("Loaded config from {:?}", config_path);
+                Ok(config.merge_command_tables()) // Return the parsed config
+            }
+            Err(e) => Err(ConfigError {
+                message: describe_parse_error(&content, &e),
+                path: config_path.to_path_buf(),
+            }),
+        },
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            // Handle file not found
+            eprintln!(
+                "Config file not found at {:?}. Creating default.",
+                config_path
+            );
+            let default_config = Config::default();
+            // Write the default config
+            write_default_config(config_path, &default_config);
+            Ok(default_config) // Return the default config
+        }
+        Err(e) => Err(ConfigError {
+            message: e.to_string(),
+            path: config_path.to_path_buf(),
+        }),
+    }
+}
+
+// Describes a TOML error on a single line, pointing at where in the file it happened
+fn describe_parse_error(content: &str, error: &toml::de::Error) -> String {
+    // The message itself may span several lines, like "invalid string\nexpected `\"`"
+    let message = error.message().lines().collect::<Vec<_>>().join(", ");
+    let Some(span) = error.span() else {
+        return message;
+    };
+    let before = content.get(..span.start).unwrap_or(content);
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+    format!("line {}, column {}: {}", line, column, message)
+}
+
+// Determines the default configuration file path following XDG Base Directory Specification
+pub fn get_config_path() -> PathBuf {
+    // Use the dirs crate to find the user's configuration directory
+    let mut config_path = dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from(".")) // Fallback to current directory if config dir not found
+        .join("putput"); // Create an app-specific subdirectory
+
+    // Create the config directory if it doesn't exist (ignore result of create_dir_all)
+    let _ = fs::create_dir_all(&config_path);
+
+    // Append the config file name
+    config_path.push("config.toml");
+    config_path
+}
+
+// Writes the default configuration to the specified path
+fn write_default_config(path: &Path, config: &Config) {
+    // Serialize the config to a pretty TOML string
+    match toml::to_string_pretty(config) {
+        Ok(toml_str) => {
+            // Ensure the parent directory exists before writing the file
+            if let Some(parent) = path.parent() {
+                if !parent.exists() {
+                    if let Err(e) = fs::create_dir_all(parent) {
+                        eprintln!("Error creating config directory {:?}: {}", parent, e);
+                        return; // Stop if directory creation fails
+                    }
+                }
+            }
+            // Write the TOML string to the file
+            if let Err(e) = fs::write(path, toml_str) {
+                eprintln!("Error writing default config file {:?}: {}", path, e);
+            } else {
+                eprintln!("Created default config at {:?}", path);
+            }
+        }
+        Err(e) => {
+            eprintln!("Error serializing default config: {}", e); // Handle serialization errors
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Config {
+        toml::from_str::<Config>(content)
+            .unwrap()
+            .merge_command_tables()
+    }
+
+    #[test]
+    fn command_tables_are_appended_to_commands() {
+        let config = parse(
+            r#"
+            run_commands_on_change = false
+            title = "Test"
+            commands = ["wc", { cmd = "tr a-z A-Z", on_change = false }]
+
+            [[command]]
+            cmd = "rev"
+            label = "Reversed"
+            timeout_secs = 5
+            "#,
+        );
+        let labels: Vec<&str> = config.commands.iter().map(CommandConfig::label).collect();
+        assert_eq!(labels, ["wc", "tr a-z A-Z", "Reversed"]);
+        assert!(!config.commands[1].on_change());
+        assert_eq!(config.commands[2].timeout_secs(), Some(5));
+        assert!(config.command_tables.is_empty());
+    }
+
+    #[test]
+    fn parse_errors_point_at_line_and_column() {
+        let content = "title = \"Test\"\nrun_commands_on_change = maybe\n";
+        let error = toml::from_str::<Config>(content).unwrap_err();
+        let message = describe_parse_error(content, &error);
+        assert!(
+            message.starts_with("line 2, column 26: "),
+            "unexpected message: {}",
+            message
+        );
+        assert!(!message.contains('\n'));
+    }
+
+    #[test]
+    fn broken_config_file_is_left_untouched() {
+        let path = std::env::temp_dir().join(format!("putput-broken-{}.toml", std::process::id()));
+        let content = "title = \"Unfinished\ncommands = [\"wc\"]\n";
+        fs::write(&path, content).unwrap();
+
+        let error = load_config(&path).unwrap_err();
+        assert_eq!(error.path, path);
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn profiles_fall_back_to_the_top_level() {
+        let config = parse(
+            r#"
+            run_commands_on_change = true
+            multiline_input = true
+            title = "Test"
+            commands = ["wc"]
+
+            [profiles.json]
+            title = "JSON"
+            run_commands_on_change = false
+            commands = ["jq ."]
+
+            [[profiles.json.command]]
+            cmd = "yq ."
+
+            [profiles.bare]
+            "#,
+        );
+
+        let json = config.for_profile(Some("json"));
+        assert_eq!(json.title, "JSON");
+        assert!(!json.run_commands_on_change);
+        assert!(json.multiline_input);
+        let commands: Vec<&str> = json.commands.iter().map(CommandConfig::cmd).collect();
+        assert_eq!(commands, ["jq .", "yq ."]);
+
+        let bare = config.for_profile(Some("bare"));
+        assert_eq!(bare.title, "bare");
+        assert!(bare.run_commands_on_change);
+        assert_eq!(bare.commands[0].cmd(), "wc");
+
+        // Unknown profiles and no profile at all both select the top-level commands
+        assert_eq!(config.for_profile(Some("missing")).title, "Test");
+        assert_eq!(config.for_profile(None).commands[0].cmd(), "wc");
+    }
+}
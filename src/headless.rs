@@ -0,0 +1,203 @@
+// --- Headless Mode ---
+// Runs the configured commands once and prints their results, without starting GTK.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::config::{CommandConfig, Config};
+use crate::runner::{run_commands_async, CommandEvent, Generations};
+
+// How headless results are printed
+#[derive(Clone, Copy, Default)]
+pub enum OutputFormat {
+    #[default]
+    Text, // Each result under its label, as the window would show it
+    Json, // One JSON object per command and line
+}
+
+impl OutputFormat {
+    // Parses the value of --format
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(OutputFormat::Text),
+            "json" => Some(OutputFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+// How a single command ended, as printed in JSON
+#[derive(Serialize)]
+struct CommandReport<'a> {
+    command: &'a str,
+    label: &'a str,
+    exit_status: Option<i32>, // Null if the command didn't exit by itself
+    success: bool,
+    stdout: String,
+    stderr: String,
+    duration_ms: u128,
+    timed_out: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>, // Why the command couldn't be run to completion
+}
+
+// What was received for one command during the run
+#[derive(Default)]
+struct CommandResult {
+    streamed: String, // Stdout received so far, kept for commands that never finish
+    event: Option<CommandEvent>, // How the command ended
+    elapsed: Duration, // Time from the start of the run until it ended
+}
+
+// Runs every command on the input and prints each result in the given format
+// Returns the process exit code: 0 if every command succeeded, 1 otherwise
+pub fn run_headless(config: Config, input: String, format: OutputFormat) -> i32 {
+    let config = Arc::new(config);
+    let (sender, receiver) = async_channel::unbounded();
+    let started = Instant::now();
+    run_commands_async(
+        input,
+        Arc::clone(&config),
+        sender, // Dropped once every command has finished, which closes the channel
+        Generations::default().next(),
+        false,
+    );
+
+    let mut results: BTreeMap<usize, CommandResult> = BTreeMap::new();
+    while let Ok(update) = receiver.recv_blocking() {
+        let result = results.entry(update.command_id).or_default();
+        match update.event {
+            CommandEvent::Started => {}
+            CommandEvent::Output(chunk) => result.streamed.push_str(&chunk),
+            event => {
+                result.event = Some(event);
+                result.elapsed = started.elapsed();
+            }
+        }
+    }
+
+    let mut exit_code = 0;
+    for (command_id, command) in config.commands.iter().enumerate() {
+        let result = results.remove(&command_id).unwrap_or_default();
+        if result.event.as_ref().is_none_or(CommandEvent::is_error) {
+            exit_code = 1;
+        }
+        match format {
+            OutputFormat::Text => print_text(command_id, command, &result),
+            OutputFormat::Json => print_json(command, result),
+        }
+    }
+    exit_code
+}
+
+// Prints a result under its label, separated from the previous one by a blank line
+fn print_text(command_id: usize, command: &CommandConfig, result: &CommandResult) {
+    if command_id > 0 {
+        println!();
+    }
+    println!("[{}]", command.label());
+    match &result.event {
+        Some(event) => println!("{}", event.result_text()),
+        None => println!("No result"),
+    }
+}
+
+// Prints a result as a single line of JSON
+fn print_json(command: &CommandConfig, result: CommandResult) {
+    match serde_json::to_string(&command_report(command, result)) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("Error serializing result for {:?}: {}", command.cmd(), e),
+    }
+}
+
+// Describes how a command ended, for printing as JSON
+fn command_report(command: &CommandConfig, result: CommandResult) -> CommandReport<'_> {
+    let mut report = CommandReport {
+        command: command.cmd(),
+        label: command.label(),
+        exit_status: None,
+        success: false,
+        stdout: result.streamed,
+        stderr: String::new(),
+        duration_ms: result.elapsed.as_millis(),
+        timed_out: false,
+        error: None,
+    };
+    match result.event {
+        Some(CommandEvent::Finished(output)) => {
+            report.exit_status = output.status.code();
+            report.success = output.status.success();
+            report.stdout = output.stdout;
+            report.stderr = output.stderr;
+            report.duration_ms = output.duration.as_millis();
+        }
+        Some(CommandEvent::TimedOut(timeout_secs)) => {
+            report.timed_out = true;
+            report.error = Some(format!("Timed out after {}s", timeout_secs));
+        }
+        Some(CommandEvent::SpawnFailed(message) | CommandEvent::Failed(message)) => {
+            report.error = Some(message);
+        }
+        Some(CommandEvent::Started | CommandEvent::Output(_)) | None => {
+            report.error = Some("No result".to_string());
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::{json, Value};
+
+    use crate::runner::CommandOutput;
+
+    #[test]
+    fn finished_command_reports_its_output() {
+        let command = CommandConfig::Plain("wc".to_string());
+        let result = CommandResult {
+            streamed: "partial".to_string(),
+            event: Some(CommandEvent::Finished(CommandOutput {
+                status: std::process::Command::new("true").status().unwrap(),
+                stdout: "1 2 12\n".to_string(),
+                stderr: String::new(),
+                duration: Duration::from_millis(3),
+            })),
+            elapsed: Duration::from_millis(5),
+        };
+        let report = serde_json::to_value(command_report(&command, result)).unwrap();
+        assert_eq!(
+            report,
+            json!({
+                "command": "wc",
+                "label": "wc",
+                "exit_status": 0,
+                "success": true,
+                "stdout": "1 2 12\n",
+                "stderr": "",
+                "duration_ms": 3,
+                "timed_out": false,
+            })
+        );
+    }
+
+    #[test]
+    fn timed_out_command_reports_an_error() {
+        let command = CommandConfig::Plain("sleep 5".to_string());
+        let result = CommandResult {
+            streamed: "so far".to_string(),
+            event: Some(CommandEvent::TimedOut(1)),
+            elapsed: Duration::from_millis(1000),
+        };
+        let report = serde_json::to_value(command_report(&command, result)).unwrap();
+        assert_eq!(report["exit_status"], Value::Null);
+        assert_eq!(report["success"], false);
+        assert_eq!(report["timed_out"], true);
+        assert_eq!(report["stdout"], "so far");
+        assert_eq!(report["duration_ms"], 1000);
+        assert_eq!(report["error"], "Timed out after 1s");
+    }
+}
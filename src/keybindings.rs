@@ -0,0 +1,310 @@
+// --- Keybindings ---
+// Maps keyboard shortcuts to the application's actions, from the defaults and the `[keybindings]` config section.
+
+use gtk::prelude::*;
+
+use std::collections::BTreeMap;
+
+// Shortcuts available without any configuration; `[keybindings]` entries override these per shortcut
+const DEFAULT_KEYBINDINGS: &[(&str, &str)] = &[
+    ("<Control>l", "clear"),
+    ("<Control>f", "focus-input"),
+    ("<Control>m", "toggle-multiline"),
+    ("<Control>p", "next-profile"),
+    ("<Control><Shift>p", "previous-profile"),
+    ("<Control>h", "hints"),
+    ("<Alt>j", "select-next"),
+    ("<Alt>k", "select-previous"),
+    ("<Control>0", "copy-all"),
+    ("<Control>KP_0", "copy-all"),
+    ("<Control>1", "copy 1"),
+    ("<Control>2", "copy 2"),
+    ("<Control>3", "copy 3"),
+    ("<Control>4", "copy 4"),
+    ("<Control>5", "copy 5"),
+    ("<Control>6", "copy 6"),
+    ("<Control>7", "copy 7"),
+    ("<Control>8", "copy 8"),
+    ("<Control>9", "copy 9"),
+    ("<Control>KP_1", "copy 1"),
+    ("<Control>KP_2", "copy 2"),
+    ("<Control>KP_3", "copy 3"),
+    ("<Control>KP_4", "copy 4"),
+    ("<Control>KP_5", "copy 5"),
+    ("<Control>KP_6", "copy 6"),
+    ("<Control>KP_7", "copy 7"),
+    ("<Control>KP_8", "copy 8"),
+    ("<Control>KP_9", "copy 9"),
+    ("<Control><Shift>1", "copy-and-hide 1"),
+    ("<Control><Shift>2", "copy-and-hide 2"),
+    ("<Control><Shift>3", "copy-and-hide 3"),
+    ("<Control><Shift>4", "copy-and-hide 4"),
+    ("<Control><Shift>5", "copy-and-hide 5"),
+    ("<Control><Shift>6", "copy-and-hide 6"),
+    ("<Control><Shift>7", "copy-and-hide 7"),
+    ("<Control><Shift>8", "copy-and-hide 8"),
+    ("<Control><Shift>9", "copy-and-hide 9"),
+];
+
+// What a shortcut does, as written in the config
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeyAction {
+    Copy(u32),        // Copies the output of the Nth command
+    CopyAndHide(u32), // Copies the output of the Nth command and closes the window
+    CopyAll,          // Copies every output, each under its label
+    Hints,            // Labels the rows with hints to type
+    SelectNext,       // Moves the selection down a row
+    SelectPrevious,   // Moves the selection up a row
+    Clear,
+    RerunAll,
+    NextProfile,
+    PreviousProfile,
+    HistoryUp,   // Goes back to an earlier input
+    HistoryDown, // Goes forward to a later input
+    FocusInput,
+    ToggleMultiline, // Switches between the single-line and multi-line input
+}
+
+impl KeyAction {
+    // Parses an action such as `clear` or `copy 2`
+    fn parse(action: &str) -> Result<Self, String> {
+        let mut words = action.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let argument = words.next();
+        if words.next().is_some() {
+            return Err(format!("too many arguments in action {:?}", action));
+        }
+
+        let number = || match argument.map(str::parse::<u32>) {
+            Some(Ok(number)) if number > 0 => Ok(number),
+            _ => Err(format!(
+                "action {:?} needs a command number, e.g. \"{} 1\"",
+                action, name
+            )),
+        };
+        let no_argument = |key_action| match argument {
+            None => Ok(key_action),
+            Some(_) => Err(format!("action {:?} doesn't take an argument", name)),
+        };
+
+        match name {
+            "copy" => number().map(KeyAction::Copy),
+            "copy-and-hide" => number().map(KeyAction::CopyAndHide),
+            "copy-all" => no_argument(KeyAction::CopyAll),
+            "hints" => no_argument(KeyAction::Hints),
+            "select-next" => no_argument(KeyAction::SelectNext),
+            "select-previous" => no_argument(KeyAction::SelectPrevious),
+            "clear" => no_argument(KeyAction::Clear),
+            "rerun-all" => no_argument(KeyAction::RerunAll),
+            "next-profile" => no_argument(KeyAction::NextProfile),
+            "previous-profile" => no_argument(KeyAction::PreviousProfile),
+            "history-up" => no_argument(KeyAction::HistoryUp),
+            "history-down" => no_argument(KeyAction::HistoryDown),
+            "focus-input" => no_argument(KeyAction::FocusInput),
+            "toggle-multiline" => no_argument(KeyAction::ToggleMultiline),
+            _ => Err(format!("unknown action {:?}", action)),
+        }
+    }
+
+    // The application action this runs, with its parameter
+    fn detailed_action_name(&self) -> String {
+        match self {
+            KeyAction::Copy(number) => format!("app.copy-result({})", number),
+            KeyAction::CopyAndHide(number) => format!("app.copy-result-and-hide({})", number),
+            KeyAction::CopyAll => "app.copy-all".to_string(),
+            KeyAction::Hints => "app.show-hints".to_string(),
+            KeyAction::SelectNext => "app.move-selection(1)".to_string(),
+            KeyAction::SelectPrevious => "app.move-selection(-1)".to_string(),
+            KeyAction::Clear => "app.clear".to_string(),
+            KeyAction::RerunAll => "app.run".to_string(),
+            KeyAction::NextProfile => "app.cycle-profile(1)".to_string(),
+            KeyAction::PreviousProfile => "app.cycle-profile(-1)".to_string(),
+            KeyAction::HistoryUp => "app.history-up".to_string(),
+            KeyAction::HistoryDown => "app.history-down".to_string(),
+            KeyAction::FocusInput => "app.focus-input".to_string(),
+            KeyAction::ToggleMultiline => "app.toggle-multiline".to_string(),
+        }
+    }
+}
+
+// Shortcuts in GTK's canonical accelerator form, each with the action it runs
+pub type Keybindings = BTreeMap<String, KeyAction>;
+
+// Parses the `[keybindings]` section on top of the defaults
+// Entries that can't be used are skipped, so the rest of the config keeps working; a warning is
+// returned for each of them, and for each default shortcut that is bound to something else
+pub fn parse_keybindings(configured: &BTreeMap<String, String>) -> (Keybindings, Vec<String>) {
+    merge_keybindings(configured, canonical_accelerator)
+}
+
+// Applies the configured keybindings on top of the defaults, with `canonical` normalizing
+// shortcuts or rejecting invalid ones
+fn merge_keybindings(
+    configured: &BTreeMap<String, String>,
+    canonical: impl Fn(&str) -> Option<String>,
+) -> (Keybindings, Vec<String>) {
+    let mut keybindings = Keybindings::new();
+    let mut default_actions: BTreeMap<String, &str> = BTreeMap::new();
+    for (accelerator, action) in DEFAULT_KEYBINDINGS {
+        let accelerator = canonical(accelerator).expect("default keybindings are valid");
+        let key_action = KeyAction::parse(action).expect("default keybindings are valid");
+        keybindings.insert(accelerator.clone(), key_action);
+        default_actions.insert(accelerator, action);
+    }
+
+    let mut configured_accelerators: BTreeMap<String, &str> = BTreeMap::new();
+    let mut warnings = Vec::new();
+    for (accelerator, action) in configured {
+        // Two spellings of the same shortcut (e.g. `<Ctrl>1` and `<Control>1`) keep the first one
+        let Some(canonical) = canonical(accelerator) else {
+            warnings.push(format!(
+                "keybindings: {:?} isn't a valid shortcut, so it's ignored",
+                accelerator
+            ));
+            continue;
+        };
+        if let Some(other) = configured_accelerators.get(&canonical) {
+            warnings.push(format!(
+                "keybindings: {:?} and {:?} are the same shortcut, so {:?} is ignored",
+                other, accelerator, accelerator
+            ));
+            continue;
+        }
+        configured_accelerators.insert(canonical.clone(), accelerator);
+
+        let key_action = match KeyAction::parse(action) {
+            Ok(key_action) => key_action,
+            Err(message) => {
+                warnings.push(format!(
+                    "keybindings: {:?}: {}, so it's ignored",
+                    accelerator, message
+                ));
+                continue;
+            }
+        };
+        if keybindings.insert(canonical.clone(), key_action) != Some(key_action) {
+            if let Some(default_action) = default_actions.get(&canonical) {
+                warnings.push(format!(
+                    "keybindings: {:?} replaces the default shortcut for {:?}",
+                    accelerator, default_action
+                ));
+            }
+        }
+    }
+    (keybindings, warnings)
+}
+
+// Normalizes an accelerator, so different spellings of the same shortcut compare equal
+// Returns None for shortcuts GTK can't parse
+fn canonical_accelerator(accelerator: &str) -> Option<String> {
+    match gtk::accelerator_parse(accelerator) {
+        Some((key, modifiers)) if key != gtk::gdk::Key::VoidSymbol => {
+            Some(gtk::accelerator_name(key, modifiers).to_string())
+        }
+        _ => None,
+    }
+}
+
+// Replaces the application's shortcuts with the given ones
+pub fn apply_keybindings(app: &gtk::Application, keybindings: &Keybindings) {
+    for action in app.list_action_descriptions() {
+        app.set_accels_for_action(&action, &[]);
+    }
+
+    let mut accelerators: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for (accelerator, action) in keybindings {
+        accelerators
+            .entry(action.detailed_action_name())
+            .or_default()
+            .push(accelerator);
+    }
+    for (action, accelerators) in accelerators {
+        app.set_accels_for_action(&action, &accelerators);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Stands in for GTK's parsing, which needs a display: `<Ctrl>` is spelled `<Control>`,
+    // and a shortcut without a key is invalid
+    fn canonical(accelerator: &str) -> Option<String> {
+        let canonical = accelerator.replace("<Ctrl>", "<Control>");
+        let key = canonical.rsplit('>').next().unwrap_or_default();
+        (!key.is_empty()).then_some(canonical)
+    }
+
+    // Configured keybindings from (shortcut, action) pairs
+    fn configured(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|(accelerator, action)| (accelerator.to_string(), action.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn actions_parse_with_their_arguments() {
+        assert_eq!(KeyAction::parse("copy 2"), Ok(KeyAction::Copy(2)));
+        assert_eq!(
+            KeyAction::parse("copy-and-hide 10"),
+            Ok(KeyAction::CopyAndHide(10))
+        );
+        assert_eq!(KeyAction::parse("clear"), Ok(KeyAction::Clear));
+        assert_eq!(
+            KeyAction::parse("toggle-multiline"),
+            Ok(KeyAction::ToggleMultiline)
+        );
+
+        assert!(KeyAction::parse("copy").is_err());
+        assert!(KeyAction::parse("copy 0").is_err());
+        assert!(KeyAction::parse("copy one").is_err());
+        assert!(KeyAction::parse("copy 1 2").is_err());
+        assert!(KeyAction::parse("clear 1").is_err());
+        assert!(KeyAction::parse("frobnicate").is_err());
+    }
+
+    #[test]
+    fn configured_shortcuts_apply_on_top_of_the_defaults() {
+        let (keybindings, warnings) =
+            merge_keybindings(&configured(&[("<Ctrl>Return", "rerun-all")]), canonical);
+        assert_eq!(keybindings["<Control>Return"], KeyAction::RerunAll);
+        assert_eq!(keybindings["<Control>l"], KeyAction::Clear);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn replacing_a_default_shortcut_is_reported() {
+        let (keybindings, warnings) = merge_keybindings(
+            &configured(&[
+                ("<Control>l", "rerun-all"),
+                ("<Control><Shift>1", "copy-and-hide 1"), // The same as the default, so no conflict
+            ]),
+            canonical,
+        );
+        assert_eq!(keybindings["<Control>l"], KeyAction::RerunAll);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("\"clear\""), "{}", warnings[0]);
+    }
+
+    #[test]
+    fn unusable_entries_are_skipped_and_reported() {
+        let (keybindings, warnings) = merge_keybindings(
+            &configured(&[
+                ("<Alt>", "clear"),               // No key
+                ("<Alt>x", "frobnicate"),         // Unknown action
+                ("<Control>Return", "rerun-all"), // Kept
+                ("<Ctrl>Return", "clear"),        // The same shortcut again
+            ]),
+            canonical,
+        );
+        assert_eq!(warnings.len(), 3, "{:?}", warnings);
+        assert!(warnings
+            .iter()
+            .any(|warning| warning.contains("same shortcut")));
+        assert_eq!(keybindings["<Control>Return"], KeyAction::RerunAll);
+        assert!(!keybindings.contains_key("<Alt>x"));
+        // The defaults are still there
+        assert_eq!(keybindings["<Control>l"], KeyAction::Clear);
+    }
+}
@@ -1,70 +1,406 @@
 use adw::prelude::*; // Use Adwaita prelude
 use adw::{
-    Application, ApplicationWindow, Clamp, EntryRow, HeaderBar, PreferencesGroup, WindowTitle,
+    Application, ApplicationWindow, Banner, Clamp, EntryRow, HeaderBar, PreferencesGroup,
+    WindowTitle,
 };
-use gtk::glib; // For channels and async
+use gtk::{gio, glib}; // For file monitoring, channels and async
 
-use serde::{Deserialize, Serialize};
-use std::fs;
-use std::io::Write;
-use std::path::PathBuf;
-use std::process::{Command, Stdio};
+use std::cell::{Cell, RefCell};
+use std::io::{self, Read};
+use std::path::{self, PathBuf};
+use std::rc::Rc;
 use std::sync::Arc;
-use std::thread;
+use std::time::Duration;
 
 use gtk::{
     gdk::{Key, ModifierType},
-    Align,
     Box, // Use gtk::Box for the main container
     Button,
+    DropDown,
     EventControllerKey,
     Orientation,
     ScrolledWindow,
+    StringList,
+    TextView,
+    ToggleButton,
+    WrapMode,
 };
 
 // Import necessary traits
 use adw::prelude::WidgetExt;
 
+mod config;
+mod headless;
+mod keybindings;
+mod output_row;
+mod runner;
+mod search_provider;
+mod service;
+mod state;
+
+use config::{get_config_path, load_config, Config, ConfigError};
+use headless::OutputFormat;
+use keybindings::{apply_keybindings, parse_keybindings, Keybindings};
+use output_row::OutputRow;
+use runner::{run_commands_async, CommandEvent, CommandUpdate, Generations};
+use state::{load_last_profile, save_last_profile};
+
 const APP_ID: &str = "com.github.bjesus.putput";
 
-// Enum for messages sent from background thread to main thread
-enum CommandUpdate {
-    Output(String, String), // Command Name, Output/Error String
+// Widgets and state shared by the window's signal handlers
+struct Ui {
+    window: ApplicationWindow,
+    window_title: WindowTitle,
+    title_override: Option<String>, // Title given on the command line, which wins over the config
+    config_path: PathBuf,
+    config_banner: Banner, // Shows why the config file couldn't be loaded
+    showing_config_warnings: Cell<bool>, // The banner shows warnings to dismiss rather than an error
+    profile_dropdown: DropDown,          // Switches between the profiles defined in the config
+    updating_profiles: Cell<bool>, // Set while the dropdown is changed from code rather than by the user
+    input_entry_row: EntryRow,
+    input_view: TextView, // Multi-line input, keeping the newlines the entry would lose
+    input_view_scroll: ScrolledWindow,
+    multiline_button: ToggleButton,
+    multiline: Cell<bool>, // Whether the multi-line input is the one in use
+    output_group: PreferencesGroup,
+    output_scroll: ScrolledWindow,
+    output_rows: RefCell<Vec<OutputRow>>, // One row per command, in config order
+    loaded_config: RefCell<Config>,       // The whole config file, with all of its profiles
+    profile: RefCell<Option<String>>,     // The selected profile; None is the top-level commands
+    config: RefCell<Arc<Config>>, // The config for the selected profile, which commands run with
+    sender: async_channel::Sender<CommandUpdate>,
+    generations: Generations, // Tracks the latest run so outdated results can be cancelled and dropped
+    pending_run: RefCell<Option<glib::SourceId>>, // Debounced run-on-change waiting for typing to pause, if any
+    config_monitor: RefCell<Option<gio::FileMonitor>>, // Kept alive to keep watching the config file
+    history: RefCell<Vec<String>>, // Inputs the commands were run on, oldest first
+    history_position: Cell<Option<usize>>, // The history entry being shown, if browsing the history
+    typed_hint: RefCell<Option<String>>, // What has been typed of a hint so far, while in hint mode
+    selected_row: Cell<Option<usize>>, // The row highlighted with the keyboard, if any
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
-struct Config {
-    run_commands_on_change: bool,
-    commands: Vec<String>,
-    title: String, // Added title field to Config
+// Options given on the command line
+#[derive(Default)]
+struct LaunchOptions {
+    config_path: Option<PathBuf>, // Use this config file instead of the default one
+    title: Option<String>,        // Overrides the title from the config
+    profile: Option<String>,      // Runs with this profile instead of the top-level commands
+    input: Option<String>,        // Prefills the input
+    run: bool,                    // Runs the commands as soon as the window opens
+    set_input: Option<String>,    // Replaces the input, also in an already running window
+    toggle: bool,                 // Hides the window if it's focused, shows it otherwise
+    show: bool,                   // Shows the window (what a plain launch does)
+    hide: bool,                   // Hides the window without quitting
+    headless: bool,               // Prints the results instead of opening a window
+    format: Option<String>,       // How headless results are printed
+    arguments: Vec<String>,       // Input given as plain arguments, for headless mode
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Config {
-            run_commands_on_change: false,
-            commands: vec!["cat".to_string(), "wc".to_string()],
-            title: "Putput".to_string(), // Default title
+impl LaunchOptions {
+    // Reads the options GApplication parsed from the command line
+    fn from_dict(dict: &glib::VariantDict) -> Self {
+        LaunchOptions {
+            config_path: dict
+                .lookup::<PathBuf>("config")
+                .ok()
+                .flatten()
+                .map(|path| path::absolute(&path).unwrap_or(path)), // The working directory may change
+            title: dict.lookup::<String>("title").ok().flatten(),
+            profile: dict.lookup::<String>("profile").ok().flatten(),
+            input: dict.lookup::<String>("input").ok().flatten(),
+            run: dict.contains("run"),
+            set_input: dict.lookup::<String>("set-input").ok().flatten(),
+            toggle: dict.contains("toggle"),
+            show: dict.contains("show"),
+            hide: dict.contains("hide"),
+            headless: dict.contains("headless"),
+            format: dict.lookup::<String>("format").ok().flatten(),
+            arguments: dict
+                .lookup::<Vec<String>>("")
+                .ok()
+                .flatten()
+                .unwrap_or_default(),
         }
     }
 }
 
-fn main() {
-    // Initialize Libadwaita (and GTK implicitly)
-    adw::init().expect("Failed to initialize Libadwaita");
+fn main() -> glib::ExitCode {
+    // Libadwaita is initialized when the application starts up, so headless mode never needs a display
+    // Launches are forwarded to the running instance, which decides what to do with them
+    let app = Application::builder()
+        .application_id(APP_ID)
+        .flags(gio::ApplicationFlags::HANDLES_COMMAND_LINE)
+        .build();
 
-    let app = Application::builder().application_id(APP_ID).build();
+    // --- Command Line Options ---
+    app.add_main_option(
+        "config",
+        glib::Char::from(b'c'),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::Filename,
+        "Use a different config file",
+        Some("PATH"),
+    );
+    app.add_main_option(
+        "title",
+        glib::Char::from(b't'),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::String,
+        "Override the title from the config",
+        Some("TITLE"),
+    );
+    app.add_main_option(
+        "profile",
+        glib::Char::from(b'p'),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::String,
+        "Use a profile from the config",
+        Some("NAME"),
+    );
+    app.add_main_option(
+        "input",
+        glib::Char::from(b'i'),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::String,
+        "Prefill the input",
+        Some("TEXT"),
+    );
+    app.add_main_option(
+        "run",
+        glib::Char::from(b'r'),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "Run the commands on launch",
+        None,
+    );
+    app.add_main_option(
+        "set-input",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::String,
+        "Replace the input of the running window",
+        Some("TEXT"),
+    );
+    app.add_main_option(
+        "toggle",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "Hide the window if it's focused, show it otherwise",
+        None,
+    );
+    app.add_main_option(
+        "show",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "Show the window",
+        None,
+    );
+    app.add_main_option(
+        "hide",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "Hide the window",
+        None,
+    );
+    app.add_main_option(
+        "headless",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "Print the results of the commands without opening a window",
+        None,
+    );
+    app.add_main_option(
+        "format",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::String,
+        "How --headless prints the results: text or json",
+        Some("FORMAT"),
+    );
+    app.add_main_option(
+        "", // Collects the remaining arguments
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::StringArray,
+        "Input for --headless, read from stdin if not given",
+        Some("[INPUT…]"),
+    );
+
+    // The config file of this instance, which the D-Bus service and the search provider use
+    // until there's a window
+    let config_path: Rc<RefCell<Option<PathBuf>>> = Rc::default();
+    let config_path_clone = Rc::clone(&config_path);
+    app.connect_handle_local_options(move |app, dict| {
+        let options = LaunchOptions::from_dict(dict);
+        if options.headless {
+            return run_headless(&options);
+        }
+        // Check the profile here, where the error reaches the terminal it was launched from,
+        // rather than in the running instance
+        if let Some(profile) = &options.profile {
+            let config_path = options.config_path.clone().unwrap_or_else(get_config_path);
+            if let Ok(config) = load_config(&config_path) {
+                if !config.profiles.contains_key(profile) {
+                    eprintln!("No such profile: {}", profile);
+                    return 2;
+                }
+            }
+        }
+        // Launchers with different configs each get their own instance instead of
+        // activating whichever one was started first
+        if let Some(config_path) = &options.config_path {
+            app.set_application_id(Some(&instance_id(config_path)));
+            // The running instance may have a different working directory
+            dict.insert_value("config", &config_path.to_variant());
+        }
+        config_path_clone.replace(options.config_path);
+        -1 // Continue with the default handling
+    });
 
-    app.connect_activate(build_ui);
+    // The window of this instance, created by the first launch and reused by every later one
+    let ui_slot: Rc<RefCell<Option<Rc<Ui>>>> = Rc::new(RefCell::new(None));
+    let ui_slot_clone = Rc::clone(&ui_slot);
+    app.connect_startup(move |app| {
+        add_actions(app, &ui_slot_clone);
+        let config_path = config_path.borrow().clone().unwrap_or_else(get_config_path);
+        service::register(app, config_path.clone(), &ui_slot_clone);
+        search_provider::register(app, config_path, &ui_slot_clone);
+    });
+    let ui_slot_clone = Rc::clone(&ui_slot);
+    app.connect_command_line(move |app, command_line| {
+        let options = LaunchOptions::from_dict(&command_line.options_dict());
+        handle_launch(app, &ui_slot_clone, &options);
+        0
+    });
+    app.connect_activate(move |app| handle_launch(app, &ui_slot, &LaunchOptions::default()));
 
-    app.run();
+    app.run() // Exits with the status of headless runs, like any other launch
 }
 
-fn build_ui(app: &Application) {
-    // Load configuration
-    let config = load_config();
-    // Use Arc for sharing config data with closures/threads
-    let config = Arc::new(config);
+// Runs the commands once and prints their results, returning the exit code
+fn run_headless(options: &LaunchOptions) -> i32 {
+    let format = match options.format.as_deref() {
+        None => OutputFormat::default(),
+        Some(name) => match OutputFormat::parse(name) {
+            Some(format) => format,
+            None => {
+                eprintln!("Unknown output format {:?}, expected text or json", name);
+                return 2;
+            }
+        },
+    };
+    let config_path = options.config_path.clone().unwrap_or_else(get_config_path);
+    let config = match load_config(&config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error loading config file {}", e);
+            return 2;
+        }
+    };
+    if let Some(profile) = &options.profile {
+        if !config.profiles.contains_key(profile) {
+            eprintln!("No such profile: {}", profile);
+            return 2;
+        }
+    }
+    let config = config.for_profile(options.profile.as_deref());
+
+    // Input comes from the arguments, then --input, then stdin
+    let input = if !options.arguments.is_empty() {
+        options.arguments.join(" ")
+    } else if let Some(input) = &options.input {
+        input.clone()
+    } else {
+        let mut input = String::new();
+        if let Err(e) = io::stdin().read_to_string(&mut input) {
+            eprintln!("Error reading input from stdin: {}", e);
+            return 2;
+        }
+        input
+    };
+
+    headless::run_headless(config, input, format)
+}
+
+// Applies a launch to this instance's window, building the window on the first launch
+fn handle_launch(app: &Application, ui_slot: &RefCell<Option<Rc<Ui>>>, options: &LaunchOptions) {
+    let existing_ui = ui_slot.borrow().clone();
+    let ui = match existing_ui {
+        Some(ui) => ui,
+        None => {
+            let ui = build_ui(app, options);
+            *ui_slot.borrow_mut() = Some(Rc::clone(&ui));
+            // --input only prefills a newly opened window
+            if let Some(input) = &options.input {
+                ui.set_input(input);
+            }
+            ui
+        }
+    };
+
+    // Select the profile before anything runs, so it runs with the profile's commands
+    if let Some(profile) = &options.profile {
+        let exists = ui.loaded_config.borrow().profiles.contains_key(profile);
+        if exists {
+            ui.select_profile(Some(profile.clone()));
+        }
+    }
+    if let Some(input) = &options.set_input {
+        ui.set_input(input);
+    }
+    // A toggle only hides a window the user is looking at; one that lost focus is raised instead
+    let toggled_off = options.toggle && ui.window.is_visible() && ui.window.is_active();
+    if options.hide || (toggled_off && !options.show) {
+        ui.hide();
+    } else {
+        ui.present();
+    }
+    if options.run {
+        ui.run_commands();
+    }
+}
+
+// Application id for an instance using a config file other than the default
+fn instance_id(config_path: &path::Path) -> String {
+    let checksum = glib::compute_checksum_for_string(
+        glib::ChecksumType::Sha256,
+        config_path.to_string_lossy().as_ref(),
+    )
+    .unwrap_or_default();
+    // D-Bus name elements can't start with a digit, so prefix the checksum
+    format!("{}.Config{}", APP_ID, &checksum[..16.min(checksum.len())])
+}
+
+// Loads the config file for the window along with its keybindings
+// Also returns warnings about the keybindings, like skipped entries or default shortcuts bound to something else
+fn load_window_config(
+    config_path: &path::Path,
+) -> Result<(Config, Keybindings, Vec<String>), ConfigError> {
+    let config = load_config(config_path)?;
+    let (keybindings, warnings) = parse_keybindings(&config.keybindings);
+    for warning in &warnings {
+        eprintln!("{}: {}", config_path.display(), warning);
+    }
+    Ok((config, keybindings, warnings))
+}
+
+fn build_ui(app: &Application, options: &LaunchOptions) -> Rc<Ui> {
+    let config_path = options.config_path.clone().unwrap_or_else(get_config_path);
+
+    // Load configuration, falling back to the defaults if the config file is broken
+    let (config, keybindings, config_warnings, config_error) =
+        match load_window_config(&config_path) {
+            Ok((config, keybindings, warnings)) => (config, keybindings, warnings, None),
+            Err(e) => {
+                eprintln!("Error loading config file {}. Using default.", e);
+                let config = Config::default();
+                let (keybindings, _) = parse_keybindings(&config.keybindings);
+                (config, keybindings, Vec::new(), Some(e))
+            }
+        };
 
     // Use the title from the config
     let app_title = config.title.clone();
@@ -78,22 +414,44 @@ fn build_ui(app: &Application) {
         .build();
 
     // Create the manual HeaderBar widget
-    let header_bar = HeaderBar::builder()
-        .title_widget(&WindowTitle::new(&app_title, "")) // Use the title from config
-        .build();
+    let window_title = WindowTitle::new(&app_title, ""); // Use the title from config
+    let header_bar = HeaderBar::builder().title_widget(&window_title).build();
 
     // --- Header Bar Buttons ---
     let clear_button = Button::from_icon_name("edit-clear-symbolic");
     clear_button.set_tooltip_text(Some("Clear Input"));
+    clear_button.set_action_name(Some("app.clear"));
     // Add the clear button to the start of the manual HeaderBar
     header_bar.pack_start(&clear_button);
 
+    // Switches between the single-line entry and the multi-line text area
+    let multiline_button = ToggleButton::builder()
+        .icon_name("format-justify-left-symbolic")
+        .tooltip_text("Multi-line Input (Ctrl+M)")
+        .build();
+    header_bar.pack_start(&multiline_button);
+
+    // Profile switcher at the end of the HeaderBar, shown once the config defines profiles
+    let profile_dropdown = DropDown::builder()
+        .tooltip_text("Switch Profile (Ctrl+P)")
+        .visible(false)
+        .build();
+    header_bar.pack_end(&profile_dropdown);
+
     // Create a vertical box to hold the header bar and the main content area
     let main_vbox = Box::new(Orientation::Vertical, 0); // 0 spacing between children
 
     // Add the manual header bar to the top of the main vertical box
     main_vbox.append(&header_bar);
 
+    // Banner below the header bar for config errors, with a button to try loading it again,
+    // and for warnings about a config that could be loaded, with a button to dismiss them
+    let config_banner = Banner::builder()
+        .button_label("Reload")
+        .use_markup(false) // Error messages may contain characters that look like markup
+        .build();
+    main_vbox.append(&config_banner);
+
     // Create main content box inside a Clamp for responsive width
     let content_box = Box::new(Orientation::Vertical, 10); // 10 spacing between content elements
     content_box.set_margin_start(10);
@@ -122,6 +480,31 @@ fn build_ui(app: &Application) {
 
     content_box.append(&input_entry_row);
 
+    // Multi-line input, shown instead of the entry row while in multi-line mode
+    let input_view = TextView::builder()
+        .monospace(true)
+        .wrap_mode(WrapMode::WordChar)
+        .top_margin(8)
+        .bottom_margin(8)
+        .left_margin(12)
+        .right_margin(12)
+        .build();
+    input_view.update_property(&[gtk::accessible::Property::Label("Input")]);
+    let input_view_scroll = ScrolledWindow::builder()
+        .child(&input_view)
+        .min_content_height(100)
+        .max_content_height(250)
+        .propagate_natural_height(true) // Grow with the input up to the maximum height
+        .hscrollbar_policy(gtk::PolicyType::Never)
+        .css_classes(["card"])
+        .visible(false)
+        .margin_top(5)
+        .margin_bottom(5)
+        .margin_start(5)
+        .margin_end(5)
+        .build();
+    content_box.append(&input_view_scroll);
+
     // --- Output Area ---
     // Use PreferencesGroup for styled grouping of outputs
     let output_group = PreferencesGroup::new();
@@ -140,349 +523,990 @@ fn build_ui(app: &Application) {
     // Channel for async communication between command threads and UI thread
     let (sender, receiver) = async_channel::unbounded::<CommandUpdate>();
 
-    // Configure command output sections using AdwEntryRow
-    // Store AdwEntryRow widgets directly for easier updates from the receiver
-    let command_output_rows: Arc<Vec<(String, EntryRow)>> = Arc::new(
-        config
-            .commands
-            .iter()
-            .map(|cmd| {
-                // Use AdwEntryRow for each command's output
-                let output_entry_row = EntryRow::builder()
-                    .title(cmd) // Use command as the title of the EntryRow
-                    .editable(false) // Output should not be editable
-                    .build();
-
-                // Create a Copy button for this command's output
-                let copy_button = Button::from_icon_name("edit-copy-symbolic");
-                copy_button.set_tooltip_text(Some("Copy Output"));
-                copy_button.set_valign(Align::Center); // Vertically align the copy button
-
-                // Clone the EntryRow for the copy button's click handler to get its text
-                let output_entry_row_clone = output_entry_row.clone();
-
-                // Connect the clicked signal of the copy button
-                copy_button.connect_clicked(move |_| {
-                    let text = output_entry_row_clone.text(); // Get text directly from EntryRow using EntryExt
-                    if let Some(display) = gtk::gdk::Display::default() {
-                        // Get the default GDK display and its clipboard
-                        display.clipboard().set_text(&text); // Set the clipboard text
-                    }
-                });
-
-                // Add the copy button as a suffix to the EntryRow
-                output_entry_row.add_suffix(&copy_button);
-
-                // Add the output EntryRow to the output group
-                output_group.add(&output_entry_row);
-
-                (cmd.clone(), output_entry_row) // Store command name and its EntryRow
-            })
-            .collect(),
-    );
+    let ui = Rc::new(Ui {
+        window: window.clone(),
+        window_title,
+        title_override: options.title.clone(),
+        config_path: config_path.clone(),
+        config_banner: config_banner.clone(),
+        showing_config_warnings: Cell::new(false),
+        profile_dropdown: profile_dropdown.clone(),
+        updating_profiles: Cell::new(false),
+        input_entry_row: input_entry_row.clone(),
+        input_view: input_view.clone(),
+        input_view_scroll,
+        multiline_button: multiline_button.clone(),
+        multiline: Cell::new(false),
+        output_group,
+        output_scroll,
+        output_rows: RefCell::default(),
+        loaded_config: RefCell::default(),
+        profile: RefCell::new(load_last_profile(&config_path)), // Start where the user left off
+        config: RefCell::new(Arc::new(Config::default())),
+        sender,
+        generations: Generations::default(),
+        pending_run: RefCell::default(),
+        config_monitor: RefCell::default(),
+        history: RefCell::default(),
+        history_position: Cell::new(None),
+        typed_hint: RefCell::default(),
+        selected_row: Cell::new(None),
+    });
+
+    // Configure command output sections and the title for the loaded config
+    ui.apply_config(config, &keybindings);
+    if let Some(e) = config_error {
+        ui.show_config_error(&e);
+    } else {
+        ui.show_config_warnings(&config_warnings);
+    }
 
     // --- Connect Signals ---
 
     // Receiver for updates from background threads
-    let command_output_rows_clone = Arc::clone(&command_output_rows);
+    let ui_clone = Rc::clone(&ui);
     glib::spawn_future_local(async move {
         // Use glib::spawn_future_local for futures that interact with the GTK main loop
         while let Ok(update) = receiver.recv().await {
-            match update {
-                CommandUpdate::Output(cmd_name, output_text) => {
-                    // Find the corresponding EntryRow and update its text on the main thread
-                    if let Some((_, entry_row)) = command_output_rows_clone
-                        .iter()
-                        .find(|(name, _)| name == &cmd_name)
-                    {
-                        entry_row.set_text(&output_text); // Set the text of the EntryRow using EntryExt
-                    }
-                }
-            }
+            ui_clone.show_update(update);
         }
     });
 
-    // Function to trigger commands (used by button and key press)
-    let trigger_run_commands = {
-        let input_entry_row_clone = input_entry_row.clone(); // Clone the EntryRow
-        let config_clone = Arc::clone(&config);
-        let sender_clone = sender.clone();
-        let command_output_rows_clone = Arc::clone(&command_output_rows); // Clone for clearing outputs
-        move || {
-            // Clear previous outputs before running new commands for a clean view
-            for (_, entry_row) in command_output_rows_clone.iter() {
-                entry_row.set_text("");
-            }
+    let ui_clone = Rc::clone(&ui);
+    input_entry_row.connect_entry_activated(move |_| ui_clone.input_activated());
 
-            // Get text directly from the input EntryRow using EntryExt
-            let text = input_entry_row_clone.text();
+    // Enter starts a new line in the multi-line input, so Ctrl+Enter runs instead
+    // The text view inserts a newline for Ctrl+Enter too, so catch it before the text view does
+    let run_controller = EventControllerKey::new();
+    run_controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+    let ui_clone = Rc::clone(&ui);
+    run_controller.connect_key_pressed(move |_, keyval, _, modifier| {
+        if matches!(keyval, Key::Return | Key::KP_Enter)
+            && modifier.contains(ModifierType::CONTROL_MASK)
+        {
+            ui_clone.input_activated();
+            return glib::Propagation::Stop;
+        }
+        glib::Propagation::Proceed
+    });
+    input_view.add_controller(run_controller);
 
-            // Spawn the async command execution
-            run_commands_async(
-                text.to_string(), // Convert GString to String
-                Arc::clone(&config_clone),
-                sender_clone.clone(),
-            );
+    // Up and Down move the selection across the results while typing
+    let selection_controller = EventControllerKey::new();
+    let ui_clone = Rc::clone(&ui);
+    selection_controller.connect_key_pressed(move |_, keyval, _, modifier| {
+        // The context menu key, or Shift+F10, opens the selected result's actions
+        let context_key = keyval == Key::Menu
+            || (keyval == Key::F10 && modifier.contains(ModifierType::SHIFT_MASK));
+        match (keyval, ui_clone.selected_row.get()) {
+            (Key::Up | Key::KP_Up, _) => ui_clone.move_selection(-1),
+            (Key::Down | Key::KP_Down, _) => ui_clone.move_selection(1),
+            (_, Some(index)) if context_key => ui_clone.show_row_menu(index),
+            // Without a selection, the context menu key opens the input's own menu
+            _ => return glib::Propagation::Proceed,
         }
-    };
+        glib::Propagation::Stop
+    });
+    input_entry_row.add_controller(selection_controller);
 
-    input_entry_row.connect_entry_activated(move |_| trigger_run_commands());
+    // Only the input in use counts; the other one changes when switching modes
+    let ui_clone = Rc::clone(&ui);
+    input_entry_row.connect_changed(move |_| {
+        if !ui_clone.multiline.get() {
+            ui_clone.input_changed();
+        }
+    });
+    let ui_clone = Rc::clone(&ui);
+    input_view.buffer().connect_changed(move |_| {
+        if ui_clone.multiline.get() {
+            ui_clone.input_changed();
+        }
+    });
 
-    let config_clone = Arc::clone(&config);
-    let sender_clone = sender.clone();
-    let command_output_rows_clone_for_change = Arc::clone(&command_output_rows); // Clone for clearing outputs on change
+    let ui_clone = Rc::clone(&ui);
+    multiline_button.connect_toggled(move |button| ui_clone.set_multiline(button.is_active()));
 
-    // Connect to the 'changed' signal directly on the input EntryRow
-    if config_clone.run_commands_on_change {
-        input_entry_row.connect_changed(move |entry_row| {
-            // Check if the config setting for running on change is enabled
-            // Clear previous outputs before running on change for a clean view
-            for (_, entry_row) in command_output_rows_clone_for_change.iter() {
-                entry_row.set_text("");
-            }
+    // Switch profiles when the user picks one from the dropdown
+    let ui_clone = Rc::clone(&ui);
+    profile_dropdown.connect_selected_notify(move |dropdown| {
+        if !ui_clone.updating_profiles.get() {
+            let profile = ui_clone.profile_at(dropdown.selected() as usize);
+            ui_clone.select_profile(profile);
+        }
+    });
 
-            // Get text directly from the EntryRow passed to the signal handler
-            let text = entry_row.text();
+    // Try loading the config again once the user has fixed it, or dismiss its warnings
+    let ui_clone = Rc::clone(&ui);
+    config_banner.connect_button_clicked(move |banner| {
+        if ui_clone.showing_config_warnings.get() {
+            banner.set_revealed(false);
+        } else {
+            ui_clone.reload_config();
+        }
+    });
 
-            // Spawn the async command execution
-            run_commands_async(
-                text.to_string(), // Convert GString to String
-                Arc::clone(&config_clone),
-                sender_clone.clone(),
-            );
-        });
+    // Watch the config file and apply changes as soon as they're saved
+    let config_file = gio::File::for_path(&config_path);
+    match config_file.monitor_file(gio::FileMonitorFlags::NONE, gio::Cancellable::NONE) {
+        Ok(monitor) => {
+            let ui_clone = Rc::clone(&ui);
+            monitor.connect_changed(move |_, _, _, event| {
+                // Editors either finish writing in place or replace the file with a new one
+                if matches!(
+                    event,
+                    gio::FileMonitorEvent::ChangesDoneHint | gio::FileMonitorEvent::Created
+                ) {
+                    ui_clone.reload_config();
+                }
+            });
+            ui.config_monitor.replace(Some(monitor));
+        }
+        Err(e) => eprintln!("Error watching config file: {}", e),
     }
 
-    // Connect Clear Button signal
-    let input_entry_row_clone_for_clear = input_entry_row.clone(); // Clone EntryRow for this closure
-    let command_output_rows_clone_for_clear = Arc::clone(&command_output_rows); // Clone for clear button
-    clear_button.connect_clicked(move |_| {
-        input_entry_row_clone_for_clear.set_text(""); // Clear the input EntryRow using EntryExt
-                                                      // Clear output fields as well for a clean state
-        for (_, entry_row) in command_output_rows_clone_for_clear.iter() {
-            entry_row.set_text("");
+    // --- Escape to Close ---
+    let key_controller = EventControllerKey::new();
+    let ui_clone = Rc::clone(&ui);
+    key_controller.connect_key_pressed(move |_, keyval, _, _| {
+        // Escape closes the window, which only hides it when staying resident
+        if keyval == Key::Escape && ui_clone.config.borrow().hide_on_escape {
+            ui_clone.window.close();
+            return glib::Propagation::Stop;
+        }
+        glib::Propagation::Proceed
+    });
+    window.add_controller(key_controller);
+
+    // --- Hint Mode ---
+    // Catches the hint letters before they reach the input
+    let hint_controller = EventControllerKey::new();
+    hint_controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+    let ui_clone = Rc::clone(&ui);
+    hint_controller.connect_key_pressed(move |_, keyval, _, modifier| {
+        if ui_clone.typed_hint.borrow().is_none() {
+            return glib::Propagation::Proceed;
+        }
+        // Leave shortcuts like Ctrl+A to their accelerators; only Shift still types a hint
+        let shortcut_modifiers = ModifierType::CONTROL_MASK
+            | ModifierType::ALT_MASK
+            | ModifierType::SUPER_MASK
+            | ModifierType::META_MASK;
+        if modifier.intersects(shortcut_modifiers) {
+            return glib::Propagation::Proceed;
+        }
+        match keyval.to_unicode() {
+            Some(key) if key.is_ascii_alphabetic() => {
+                ui_clone.type_hint(key.to_ascii_lowercase());
+                glib::Propagation::Stop
+            }
+            // Shift and friends are pressed on their own before the key they modify
+            _ if matches!(
+                keyval,
+                Key::Shift_L
+                    | Key::Shift_R
+                    | Key::Control_L
+                    | Key::Control_R
+                    | Key::Alt_L
+                    | Key::Alt_R
+                    | Key::Super_L
+                    | Key::Super_R
+            ) =>
+            {
+                glib::Propagation::Proceed
+            }
+            // Any other key, like Escape, leaves hint mode
+            _ => {
+                ui_clone.hide_hints();
+                glib::Propagation::Stop
+            }
         }
     });
+    window.add_controller(hint_controller);
+
+    // Keep the window around when staying resident, so it reopens without reloading anything
+    let ui_clone = Rc::clone(&ui);
+    window.connect_close_request(move |_| {
+        if ui_clone.config.borrow().stay_resident {
+            ui_clone.hide();
+            glib::Propagation::Stop
+        } else {
+            glib::Propagation::Proceed
+        }
+    });
+    let ui_clone = Rc::clone(&ui);
+    window.connect_is_active_notify(move |window| {
+        if !window.is_active()
+            && window.is_visible()
+            && ui_clone.config.borrow().hide_on_focus_loss
+            && !has_open_dialog(window)
+        {
+            window.close();
+        }
+    });
+
+    // Set main content; the window is shown by whichever launch asked for it
+    window.set_content(Some(&main_vbox));
+
+    ui
+}
+
+// --- Actions ---
+// Every operation is an application action, so menus, shortcuts and scripts
+// (`gapplication action com.github.bjesus.putput clear`) all go through the same code
+// They're added at startup, so scripts can call them before a window exists; until then they do nothing
+fn add_actions(app: &Application, ui_slot: &Rc<RefCell<Option<Rc<Ui>>>>) {
+    let ui_slot_clone = Rc::clone(ui_slot);
+    let clear = gio::ActionEntry::builder("clear")
+        .activate(move |_: &Application, _, _| with_ui(&ui_slot_clone, |ui| ui.clear()))
+        .build();
 
-    // --- Ctrl+Number Copy Shortcuts ---
-    // This controller remains on the window for global shortcuts
-    let key_controller_copy = EventControllerKey::new(); // Controller for copy shortcuts
-    let command_output_rows_clone_for_copy = Arc::clone(&command_output_rows); // Clone for copy handler
-
-    key_controller_copy.connect_key_pressed(move |_, keyval, _, modifier| {
-        // Check for Ctrl modifier
-        if modifier.contains(ModifierType::CONTROL_MASK) {
-            // Check if the key is a number key (1-9) using the correct Key variants
-            let index = match keyval {
-                Key::_1 => Some(0),
-                Key::_2 => Some(1),
-                Key::_3 => Some(2),
-                Key::_4 => Some(3),
-                Key::_5 => Some(4),
-                Key::_6 => Some(5),
-                Key::_7 => Some(6),
-                Key::_8 => Some(7),
-                Key::_9 => Some(8),
-                // Also check Numpad keys
-                Key::KP_1 => Some(0),
-                Key::KP_2 => Some(1),
-                Key::KP_3 => Some(2),
-                Key::KP_4 => Some(3),
-                Key::KP_5 => Some(4),
-                Key::KP_6 => Some(5),
-                Key::KP_7 => Some(6),
-                Key::KP_8 => Some(7),
-                Key::KP_9 => Some(8),
-                _ => None, // Not a number key we care about
+    let ui_slot_clone = Rc::clone(ui_slot);
+    let run = gio::ActionEntry::builder("run")
+        .activate(move |_: &Application, _, _| with_ui(&ui_slot_clone, |ui| ui.run_commands()))
+        .build();
+
+    // Takes the command's number, counting from 1 like the Ctrl+Number shortcuts
+    let ui_slot_clone = Rc::clone(ui_slot);
+    let copy_result = gio::ActionEntry::builder("copy-result")
+        .parameter_type(Some(&i32::static_variant_type()))
+        .activate(move |_: &Application, _, parameter| {
+            let Some(number) = parameter.and_then(|parameter| parameter.get::<i32>()) else {
+                return;
+            };
+            with_ui(&ui_slot_clone, |ui| {
+                if number < 1 || !ui.copy_output(number as usize - 1) {
+                    eprintln!("No command output available for number {}.", number);
+                }
+            });
+        })
+        .build();
+
+    // Like copy-result, then gets out of the way like a launcher would
+    let ui_slot_clone = Rc::clone(ui_slot);
+    let copy_result_and_hide = gio::ActionEntry::builder("copy-result-and-hide")
+        .parameter_type(Some(&i32::static_variant_type()))
+        .activate(move |_: &Application, _, parameter| {
+            let Some(number) = parameter.and_then(|parameter| parameter.get::<i32>()) else {
+                return;
             };
+            with_ui(&ui_slot_clone, |ui| {
+                if number < 1 || !ui.copy_and_dismiss(number as usize - 1) {
+                    eprintln!("No command output available for number {}.", number);
+                }
+            });
+        })
+        .build();
+
+    let ui_slot_clone = Rc::clone(ui_slot);
+    let copy_all = gio::ActionEntry::builder("copy-all")
+        .activate(move |_: &Application, _, _| with_ui(&ui_slot_clone, |ui| ui.copy_all()))
+        .build();
+
+    let ui_slot_clone = Rc::clone(ui_slot);
+    let show_hints = gio::ActionEntry::builder("show-hints")
+        .activate(move |_: &Application, _, _| with_ui(&ui_slot_clone, |ui| ui.show_hints()))
+        .build();
+
+    // Takes the number of rows to move the selection by, negative to move up
+    let ui_slot_clone = Rc::clone(ui_slot);
+    let move_selection = gio::ActionEntry::builder("move-selection")
+        .parameter_type(Some(&i32::static_variant_type()))
+        .activate(move |_: &Application, _, parameter| {
+            if let Some(step) = parameter.and_then(|parameter| parameter.get::<i32>()) {
+                with_ui(&ui_slot_clone, |ui| ui.move_selection(step as isize));
+            }
+        })
+        .build();
+
+    let ui_slot_clone = Rc::clone(ui_slot);
+    let history_up = gio::ActionEntry::builder("history-up")
+        .activate(move |_: &Application, _, _| with_ui(&ui_slot_clone, |ui| ui.browse_history(-1)))
+        .build();
+
+    let ui_slot_clone = Rc::clone(ui_slot);
+    let history_down = gio::ActionEntry::builder("history-down")
+        .activate(move |_: &Application, _, _| with_ui(&ui_slot_clone, |ui| ui.browse_history(1)))
+        .build();
 
-            if let Some(index) = index {
-                // Safely access the command_output_rows vector
-                if let Some((_, entry_row)) = command_output_rows_clone_for_copy.get(index) {
-                    let text = entry_row.text(); // Get text from the EntryRow
-                    if let Some(display) = gtk::gdk::Display::default() {
-                        display.clipboard().set_text(&text); // Set the clipboard text
-                    }
-                    glib::Propagation::Stop // Stop propagation as we handled the shortcut
+    let ui_slot_clone = Rc::clone(ui_slot);
+    let focus_input = gio::ActionEntry::builder("focus-input")
+        .activate(move |_: &Application, _, _| with_ui(&ui_slot_clone, |ui| ui.present()))
+        .build();
+
+    let ui_slot_clone = Rc::clone(ui_slot);
+    let toggle_multiline = gio::ActionEntry::builder("toggle-multiline")
+        .activate(move |_: &Application, _, _| {
+            with_ui(&ui_slot_clone, |ui| ui.set_multiline(!ui.multiline.get()))
+        })
+        .build();
+
+    // Takes the profile's name; an empty name switches to the top-level commands
+    let ui_slot_clone = Rc::clone(ui_slot);
+    let switch_profile = gio::ActionEntry::builder("switch-profile")
+        .parameter_type(Some(&String::static_variant_type()))
+        .activate(move |_: &Application, _, parameter| {
+            let Some(name) = parameter.and_then(|parameter| parameter.get::<String>()) else {
+                return;
+            };
+            with_ui(&ui_slot_clone, |ui| {
+                if name.is_empty() {
+                    ui.select_profile(None);
+                } else if ui.loaded_config.borrow().profiles.contains_key(&name) {
+                    ui.select_profile(Some(name));
                 } else {
-                    // Index is out of bounds (e.g., Ctrl+3 but only 2 commands defined)
-                    println!("No command output available for index {}.", index); // Optional feedback
-                    glib::Propagation::Proceed // Let other handlers potentially process
+                    eprintln!("No such profile: {}", name);
                 }
-            } else {
-                glib::Propagation::Proceed // Not a Ctrl+Number shortcut
+            });
+        })
+        .build();
+
+    // Takes the number of profiles to move by, negative to go backwards
+    let ui_slot_clone = Rc::clone(ui_slot);
+    let cycle_profile = gio::ActionEntry::builder("cycle-profile")
+        .parameter_type(Some(&i32::static_variant_type()))
+        .activate(move |_: &Application, _, parameter| {
+            if let Some(step) = parameter.and_then(|parameter| parameter.get::<i32>()) {
+                with_ui(&ui_slot_clone, |ui| ui.cycle_profile(step as isize));
             }
+        })
+        .build();
+
+    app.add_action_entries([
+        clear,
+        run,
+        copy_result,
+        copy_result_and_hide,
+        copy_all,
+        show_hints,
+        move_selection,
+        history_up,
+        history_down,
+        focus_input,
+        toggle_multiline,
+        switch_profile,
+        cycle_profile,
+    ]);
+}
+
+// Runs an action on the window, if a launch has built it yet
+fn with_ui(ui_slot: &RefCell<Option<Rc<Ui>>>, action: impl FnOnce(&Rc<Ui>)) {
+    let ui = ui_slot.borrow().clone(); // Not borrowed while the action runs
+    if let Some(ui) = ui {
+        action(&ui);
+    }
+}
+
+impl Ui {
+    // Shows the window and focuses the input
+    fn present(&self) {
+        // Set initial focus to the input EntryRow after the window is presented
+        // Using grab_focus() requests focus. GTK will handle it when possible.
+        self.window.present(); // Present the window first
+        self.focus_input(); // Request focus for the input in use
+    }
+
+    // Focuses whichever input is in use
+    fn focus_input(&self) {
+        if self.multiline.get() {
+            self.input_view.grab_focus();
         } else {
-            glib::Propagation::Proceed // Ctrl key not pressed
+            self.input_entry_row.grab_focus();
         }
-    });
-    window.add_controller(key_controller_copy); // Add the copy key controller to the window
+    }
 
-    // Set main content and show window
-    window.set_content(Some(&main_vbox));
+    // The text of whichever input is in use
+    fn input_text(&self) -> String {
+        if self.multiline.get() {
+            let buffer = self.input_view.buffer();
+            buffer
+                .text(&buffer.start_iter(), &buffer.end_iter(), false)
+                .to_string()
+        } else {
+            self.input_entry_row.text().to_string()
+        }
+    }
 
-    // Set initial focus to the input EntryRow after the window is presented
-    // Using grab_focus() requests focus. GTK will handle it when possible.
-    window.present(); // Present the window first
-    input_entry_row.grab_focus(); // Request focus for the input EntryRow
-}
+    // Switches between the single-line and multi-line input, carrying the text over
+    // The entry can't hold newlines, so lines are joined with spaces when leaving multi-line mode
+    fn set_multiline(&self, multiline: bool) {
+        if self.multiline.get() == multiline {
+            return;
+        }
 
-// Runs commands in separate threads and sends updates via channel
-fn run_commands_async(
-    input: String,
-    config: Arc<Config>,
-    sender: async_channel::Sender<CommandUpdate>,
-) {
-    // Iterate over each command defined in the configuration
-    for cmd_str in config.commands.iter() {
-        let command = cmd_str.clone(); // Clone the command string for the thread
-        let input_clone = input.clone(); // Clone the input string for the thread
-        let sender_clone = sender.clone(); // Clone the channel sender for the thread
-
-        // Spawn a new OS thread to execute the command in the background
-        thread::spawn(move || {
-            // Execute the command and get the output
-            let output = execute_command(&command, &input_clone);
-            // Send the command name and its output back to the main thread via the channel
-            // Use send_blocking because we are in a synchronous thread
-            if let Err(e) = sender_clone.send_blocking(CommandUpdate::Output(command, output)) {
-                eprintln!("Failed to send command output to main thread: {}", e);
+        // Copy the text while the target input is still ignored, so nothing runs again
+        let input = self.input_text();
+        if multiline {
+            let buffer = self.input_view.buffer();
+            buffer.set_text(&input);
+            buffer.place_cursor(&buffer.end_iter());
+        } else {
+            self.input_entry_row
+                .set_text(&input.lines().collect::<Vec<_>>().join(" "));
+            self.input_entry_row.set_position(-1);
+        }
+        self.multiline.set(multiline);
+
+        self.input_entry_row.set_visible(!multiline);
+        self.input_view_scroll.set_visible(multiline);
+        self.multiline_button.set_active(multiline); // Toggling it calls back here, which is a no-op now
+        self.focus_input();
+    }
+
+    // Runs the commands, or copies the selected result if there is one (Enter, or Ctrl+Enter in multi-line mode)
+    fn input_activated(&self) {
+        match self.selected_row.get() {
+            Some(index) => {
+                self.copy_output(index);
             }
+            None => self.run_commands(),
+        }
+    }
+
+    // Remembers an input the commands were run on, and stops browsing the history
+    fn add_to_history(&self, input: &str) {
+        self.history_position.set(None);
+        let mut history = self.history.borrow_mut();
+        if !input.is_empty() && history.last().is_none_or(|last| last != input) {
+            history.push(input.to_string());
+        }
+    }
+
+    // Moves `step` entries through the history, negative going back to earlier inputs
+    // Moving past the latest entry leaves the history with an empty input
+    fn browse_history(&self, step: isize) {
+        let length = self.history.borrow().len() as isize;
+        let position = self.history_position.get().map_or(length, |p| p as isize) + step;
+        if position < 0 || position > length {
+            return; // Already at the oldest input, or not browsing the history
+        }
+        let input = if position < length {
+            self.history_position.set(Some(position as usize));
+            self.history.borrow()[position as usize].clone()
+        } else {
+            self.history_position.set(None);
+            String::new()
+        };
+        self.set_input(&input);
+    }
+
+    // Hides the window without quitting, clearing the input if configured to
+    fn hide(&self) {
+        self.window.set_visible(false);
+        if self.config.borrow().clear_input_on_hide {
+            self.clear();
+        }
+    }
+
+    // Replaces the input, as if it had been typed
+    fn set_input(&self, input: &str) {
+        if self.multiline.get() {
+            let buffer = self.input_view.buffer();
+            buffer.set_text(input);
+            buffer.place_cursor(&buffer.end_iter()); // Put the cursor after the text
+        } else {
+            self.input_entry_row.set_text(input);
+            self.input_entry_row.set_position(-1); // Put the cursor after the text
+        }
+    }
+
+    // Runs every command on the current input (used by Enter)
+    fn run_commands(&self) {
+        // A debounced run would supersede this one, so drop it
+        cancel_pending_run(&self.pending_run);
+
+        // Clear previous outputs before running new commands for a clean view
+        clear_output_rows(&self.output_rows.borrow());
+
+        let text = self.input_text();
+        self.add_to_history(&text);
+
+        // Spawn the async command execution
+        run_commands_async(
+            text,
+            Arc::clone(&self.config.borrow()),
+            self.sender.clone(),
+            self.generations.next(), // Supersede any run still in flight
+            false, // Run every command, including those excluded from on-change runs
+        );
+    }
+
+    // Runs the on-change commands once typing pauses, if the config asks for it
+    fn input_changed(self: &Rc<Self>) {
+        self.select_row(None); // The results are about to change
+
+        let config = Arc::clone(&self.config.borrow());
+        if !config.run_commands_on_change {
+            return;
+        }
+
+        // Clear the previous outputs of the commands about to run; the others keep theirs until Enter
+        for (output_row, command) in self.output_rows.borrow().iter().zip(&config.commands) {
+            if command.on_change() {
+                clear_output_row(output_row);
+            } else {
+                set_row_running(output_row, false); // Its run in flight is cancelled below
+            }
+        }
+
+        // Every keystroke restarts the debounce timer and cancels the run in flight
+        cancel_pending_run(&self.pending_run);
+        self.generations.next();
+
+        let text = self.input_text();
+
+        // Wait for more input before running anything
+        if text.chars().count() < config.run_on_change_min_length {
+            return;
+        }
+
+        // Spawn the async command execution once typing pauses
+        let debounce = Duration::from_millis(config.run_on_change_debounce_ms);
+        let ui = Rc::clone(self);
+        let source_id = glib::timeout_add_local_once(debounce, move || {
+            ui.pending_run.borrow_mut().take(); // The timer has fired, so there's nothing to cancel
+            run_commands_async(text, config, ui.sender.clone(), ui.generations.next(), true);
         });
+        self.pending_run.borrow_mut().replace(source_id);
     }
-}
 
-// Executes a single command, writes input to its stdin, and captures stdout/stderr
-fn execute_command(cmd_str: &str, input: &str) -> String {
-    // Split the command string into program name and arguments
-    let cmd_parts: Vec<&str> = cmd_str.split_whitespace().collect();
-    if cmd_parts.is_empty() {
-        return "Error: Empty command".to_string();
-    }
-
-    let program = cmd_parts[0]; // The first part is the program name
-    let args = &cmd_parts[1..]; // The rest are arguments
-
-    // Attempt to spawn the command
-    match Command::new(program)
-        .args(args) // Pass the arguments
-        .stdin(Stdio::piped()) // Pipe stdin so we can write to it
-        .stdout(Stdio::piped()) // Pipe stdout to capture output
-        .stderr(Stdio::piped()) // Pipe stderr to capture errors
-        .spawn() // Spawn the child process
-    {
-        Ok(mut child) => {
-            // If the command spawned successfully, write input to its stdin
-            if let Some(mut stdin) = child.stdin.take() {
-                // Take ownership of stdin handle
-                match stdin.write_all(input.as_bytes()) {
-                    Ok(_) => {} // Writing successful
-                    Err(e) => return format!("Error writing to stdin: {}", e), // Handle write error
-                }
-                drop(stdin); // Explicitly drop stdin to close the pipe, signaling end of input to the child
+    // Clears the input and outputs, cancelling anything still running
+    fn clear(&self) {
+        cancel_pending_run(&self.pending_run);
+        self.generations.next(); // Cancel any run still in flight
+        self.set_input("");
+        clear_output_rows(&self.output_rows.borrow()); // Clear output fields as well for a clean state
+    }
+
+    // Copies the output of the command at `index`; returns false if there's no such command
+    fn copy_output(&self, index: usize) -> bool {
+        match self.output_rows.borrow().get(index) {
+            Some(output_row) => {
+                copy_to_clipboard(&output_row.text());
+                true
             }
+            None => false,
+        }
+    }
 
-            // Wait for the command to finish and collect its output
-            match child.wait_with_output() {
-                Ok(output) => {
-                    // Check if the command exited successfully
-                    if output.status.success() {
-                        // If successful, return the standard output as a String
-                        // Trim trailing whitespace (including newlines)
-                        String::from_utf8_lossy(&output.stdout).trim_end().to_string()
-                    } else {
-                        // If failed, return the status code and standard error as a formatted String
-                        // Trim trailing whitespace (including newlines) from stderr as well
-                        format!(
-                            "Failed ({}):\n{}",
-                            output.status,
-                            String::from_utf8_lossy(&output.stderr).trim_end()
-                        )
-                    }
-                }
-                Err(e) => format!("Failed to get command output: {}", e), // Handle error waiting for output
+    // Copies every output, each under its label like `putput --headless` prints them
+    fn copy_all(&self) {
+        let text = self
+            .output_rows
+            .borrow()
+            .iter()
+            .map(|output_row| format!("[{}]\n{}", output_row.title(), output_row.text().trim_end()))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        copy_to_clipboard(&text);
+    }
+
+    // Labels every row with a hint; typing a hint copies that row's output
+    fn show_hints(&self) {
+        let output_rows = self.output_rows.borrow();
+        for (output_row, hint) in output_rows.iter().zip(hint_labels(output_rows.len())) {
+            output_row.hint_label.set_text(&hint);
+            output_row.hint_label.set_visible(true);
+        }
+        self.typed_hint.replace(Some(String::new()));
+    }
+
+    // Leaves hint mode
+    fn hide_hints(&self) {
+        for output_row in self.output_rows.borrow().iter() {
+            output_row.hint_label.set_visible(false);
+        }
+        self.typed_hint.replace(None);
+    }
+
+    // Takes the next letter of a hint, copying the row once its whole hint has been typed
+    fn type_hint(&self, key: char) {
+        let Some(mut typed) = self.typed_hint.take() else {
+            return;
+        };
+        typed.push(key);
+
+        let hints = hint_labels(self.output_rows.borrow().len());
+        if let Some(index) = hints.iter().position(|hint| *hint == typed) {
+            self.hide_hints();
+            self.copy_output(index);
+        } else if hints.iter().any(|hint| hint.starts_with(&typed)) {
+            // Only keep showing the hints that can still be completed
+            for (output_row, hint) in self.output_rows.borrow().iter().zip(&hints) {
+                output_row.hint_label.set_visible(hint.starts_with(&typed));
             }
+            self.typed_hint.replace(Some(typed));
+        } else {
+            self.hide_hints();
         }
-        Err(e) => format!("Failed to execute '{}': {}", cmd_str, e), // Handle error spawning command
     }
-}
 
-// --- Config Loading and Saving ---
-
-// Gets the path to the configuration file following XDG Base Directory Specification
-fn load_config() -> Config {
-    let config_path = get_config_path();
-    // Attempt to read the config file
-    match fs::read_to_string(&config_path) {
-        Ok(content) => match toml::from_str(&content) {
-            Ok(config) => {
-                println!("Loaded config from {:?}", config_path);
-                config // Return the parsed config
+    // Moves the selection `step` rows down, or up if negative, stopping at either end
+    // Without a selection, moving down selects the first row and moving up the last
+    fn move_selection(&self, step: isize) {
+        let count = self.output_rows.borrow().len() as isize;
+        if count == 0 {
+            return;
+        }
+        let index = match self.selected_row.get() {
+            Some(index) => (index as isize + step).clamp(0, count - 1),
+            None if step > 0 => 0,
+            None => count - 1,
+        };
+        self.select_row(Some(index as usize));
+    }
+
+    // Highlights the row at `index`, scrolling it into view; None removes the highlight
+    fn select_row(&self, index: Option<usize>) {
+        let output_rows = self.output_rows.borrow();
+        if let Some(output_row) = self.selected_row.get().and_then(|i| output_rows.get(i)) {
+            output_row.row.unset_state_flags(gtk::StateFlags::SELECTED);
+        }
+        self.selected_row.set(index);
+
+        let Some(output_row) = index.and_then(|i| output_rows.get(i)) else {
+            return;
+        };
+        output_row
+            .row
+            .set_state_flags(gtk::StateFlags::SELECTED, false);
+
+        // Scroll just far enough for the whole row to be visible
+        if let Some(bounds) = output_row.row.compute_bounds(&self.output_group) {
+            let adjustment = self.output_scroll.vadjustment();
+            let top = bounds.y() as f64;
+            let bottom = top + bounds.height() as f64;
+            if top < adjustment.value() {
+                adjustment.set_value(top);
+            } else if bottom > adjustment.value() + adjustment.page_size() {
+                adjustment.set_value(bottom - adjustment.page_size());
             }
-            Err(e) => {
-                // Handle TOML parsing errors
-                eprintln!(
-                    "Error parsing config file {:?}: {}. Using default.",
-                    config_path, e
-                );
-                let default_config = Config::default();
-                // Attempt to write a default config (might fix a corrupted file)
-                write_default_config(&config_path, &default_config);
-                default_config // Return the default config
+        }
+    }
+
+    // Opens a menu with what can be done with the row at `index`
+    fn show_row_menu(&self, index: usize) {
+        let output_rows = self.output_rows.borrow();
+        let Some(output_row) = output_rows.get(index) else {
+            return;
+        };
+        let number = index + 1;
+        let menu = gio::Menu::new();
+        menu.append(Some("Copy"), Some(&format!("app.copy-result({})", number)));
+        menu.append(
+            Some("Copy and Hide"),
+            Some(&format!("app.copy-result-and-hide({})", number)),
+        );
+
+        let popover = gtk::PopoverMenu::from_model(Some(&menu));
+        popover.set_parent(&output_row.row);
+        popover.connect_closed(|popover| {
+            // The menu's action runs after it closes, so only detach it once that's done
+            let popover = popover.clone();
+            glib::idle_add_local_once(move || popover.unparent());
+        });
+        popover.popup();
+    }
+
+    // Copies the output of the command at `index` and closes the window, which hides it when
+    // staying resident; returns false if there's no such command
+    fn copy_and_dismiss(&self, index: usize) -> bool {
+        if !self.copy_output(index) {
+            return false;
+        }
+        if self.config.borrow().clear_input_on_dismiss {
+            self.clear();
+        }
+        if self.config.borrow().stay_resident {
+            self.window.close();
+            return true;
+        }
+        // Closing the last window quits, which would take the clipboard's contents along,
+        // so hand them to the clipboard manager first and close once it has them
+        self.window.set_visible(false);
+        let window = self.window.clone();
+        glib::spawn_future_local(async move {
+            if let Err(e) = window
+                .clipboard()
+                .store_future(glib::Priority::DEFAULT)
+                .await
+            {
+                eprintln!("Error storing the clipboard: {}", e);
             }
-        },
-        Err(_) => {
-            // Handle file not found or read errors
-            println!(
-                "Config file not found at {:?}. Creating default.",
-                config_path
-            );
-            let default_config = Config::default();
-            // Write the default config
-            write_default_config(&config_path, &default_config);
-            default_config // Return the default config
+            window.close();
+        });
+        true
+    }
+
+    // Applies an update from a command's background thread to its row
+    fn show_update(&self, update: CommandUpdate) {
+        // Drop updates from runs that newer input has already superseded
+        if !self.generations.is_current(update.generation) {
+            return;
+        }
+        // Find the corresponding row and update it on the main thread
+        if let Some(output_row) = self.output_rows.borrow().get(update.command_id) {
+            show_command_event(output_row, update.event);
+        }
+    }
+
+    // Loads the config file again, keeping the current config if it's still broken
+    // The input is kept, and run again right away if the new config runs commands on change
+    fn reload_config(self: &Rc<Self>) {
+        match load_window_config(&self.config_path) {
+            Ok((config, keybindings, warnings)) => {
+                self.apply_config(config, &keybindings);
+                self.show_config_warnings(&warnings);
+                self.input_changed();
+            }
+            Err(e) => self.show_config_error(&e),
+        }
+    }
+
+    // Shows why the config file couldn't be loaded
+    fn show_config_error(&self, error: &ConfigError) {
+        self.showing_config_warnings.set(false);
+        self.config_banner.set_button_label(Some("Reload"));
+        self.config_banner
+            .set_title(&format!("Couldn't load config: {}", error));
+        self.config_banner.set_revealed(true);
+    }
+
+    // Shows what looks wrong in a config file that could still be loaded, hiding the banner if nothing does
+    fn show_config_warnings(&self, warnings: &[String]) {
+        self.showing_config_warnings.set(!warnings.is_empty());
+        self.config_banner.set_button_label(Some("Dismiss"));
+        self.config_banner.set_title(&warnings.join("\n"));
+        self.config_banner.set_revealed(!warnings.is_empty());
+    }
+
+    // Takes a newly loaded config file, keeping the selected profile if it still exists
+    fn apply_config(&self, config: Config, keybindings: &Keybindings) {
+        let mut profile = self.profile.borrow_mut();
+        if profile
+            .as_ref()
+            .is_some_and(|name| !config.profiles.contains_key(name))
+        {
+            *profile = None;
+        }
+        drop(profile);
+
+        *self.loaded_config.borrow_mut() = config;
+        if let Some(app) = self.window.application() {
+            apply_keybindings(&app, keybindings);
+        }
+        self.update_profile_dropdown();
+        self.show_profile();
+    }
+
+    // Fills the profile dropdown from the loaded config and selects the current profile
+    fn update_profile_dropdown(&self) {
+        let loaded_config = self.loaded_config.borrow();
+        let labels: Vec<&str> = std::iter::once("Default")
+            .chain(loaded_config.profiles.keys().map(String::as_str))
+            .collect();
+
+        self.updating_profiles.set(true);
+        self.profile_dropdown
+            .set_model(Some(&StringList::new(&labels)));
+        self.profile_dropdown
+            .set_selected(self.profile_index() as u32);
+        self.updating_profiles.set(false);
+        self.profile_dropdown
+            .set_visible(!loaded_config.profiles.is_empty());
+    }
+
+    // The profile at a position in the dropdown; the first entry is the top-level commands
+    fn profile_at(&self, index: usize) -> Option<String> {
+        index.checked_sub(1).and_then(|index| {
+            self.loaded_config
+                .borrow()
+                .profiles
+                .keys()
+                .nth(index)
+                .cloned()
+        })
+    }
+
+    // Position of the selected profile in the dropdown
+    fn profile_index(&self) -> usize {
+        let profile = self.profile.borrow();
+        self.loaded_config
+            .borrow()
+            .profiles
+            .keys()
+            .position(|name| Some(name) == profile.as_ref())
+            .map_or(0, |index| index + 1)
+    }
+
+    // Switches to a profile, remembering it for the next launch
+    fn select_profile(self: &Rc<Self>, profile: Option<String>) {
+        save_last_profile(&self.config_path, profile.as_deref());
+        self.profile.replace(profile);
+
+        self.updating_profiles.set(true);
+        self.profile_dropdown
+            .set_selected(self.profile_index() as u32);
+        self.updating_profiles.set(false);
+
+        self.show_profile();
+        self.input_changed(); // Show results for the current input right away if running on change
+    }
+
+    // Moves `step` profiles forwards or backwards, wrapping around at either end
+    fn cycle_profile(self: &Rc<Self>, step: isize) {
+        let count = self.loaded_config.borrow().profiles.len() as isize + 1;
+        if count == 1 {
+            return; // Only the top-level commands, nothing to switch to
+        }
+        let index = (self.profile_index() as isize + step).rem_euclid(count);
+        self.select_profile(self.profile_at(index as usize));
+    }
+
+    // Rebuilds the title and output rows for the selected profile
+    fn show_profile(&self) {
+        let config = self
+            .loaded_config
+            .borrow()
+            .for_profile(self.profile.borrow().as_deref());
+
+        // Results from the old commands would land in the wrong rows, so cancel them
+        cancel_pending_run(&self.pending_run);
+        self.generations.next();
+
+        let title = self.title_override.as_ref().unwrap_or(&config.title);
+        self.window.set_title(Some(title));
+        self.window_title.set_title(title);
+
+        self.typed_hint.replace(None); // The hints belong to the old rows
+        self.selected_row.set(None);
+        let mut output_rows = self.output_rows.borrow_mut();
+        for output_row in output_rows.drain(..) {
+            self.output_group.remove(&output_row.row);
+        }
+        for (index, command) in config.commands.iter().enumerate() {
+            let output_row = OutputRow::new(index, command);
+            self.output_group.add(&output_row.row);
+            output_rows.push(output_row);
+        }
+
+        // Follow the config's input mode when it changes, keeping one toggled by hand otherwise
+        let multiline = config.multiline_input;
+        let multiline_changed = self.config.borrow().multiline_input != multiline;
+        *self.config.borrow_mut() = Arc::new(config);
+        drop(output_rows);
+        if multiline_changed {
+            self.set_multiline(multiline);
         }
     }
 }
 
-// Determines the configuration file path
-fn get_config_path() -> PathBuf {
-    // Use the dirs crate to find the user's configuration directory
-    let mut config_path = dirs::config_dir()
-        .unwrap_or_else(|| PathBuf::from(".")) // Fallback to current directory if config dir not found
-        .join("putput"); // Create an app-specific subdirectory
+// Letters hints are made of, home row first so the most common hints are the easiest to type
+const HINT_KEYS: &str = "asdfghjklqwertyuiopzxcvbnm";
 
-    // Create the config directory if it doesn't exist (ignore result of create_dir_all)
-    let _ = fs::create_dir_all(&config_path);
+// Hints for `count` rows: single letters while there are enough, two-letter sequences otherwise
+fn hint_labels(count: usize) -> Vec<String> {
+    let keys: Vec<char> = HINT_KEYS.chars().collect();
+    if count <= keys.len() {
+        return keys.iter().take(count).map(char::to_string).collect();
+    }
+    keys.iter()
+        .flat_map(|first| {
+            keys.iter()
+                .map(move |second| format!("{}{}", first, second))
+        })
+        .take(count)
+        .collect()
+}
 
-    // Append the config file name
-    config_path.push("config.toml");
-    config_path
+// Whether one of the window's dialogs, like a result's full output, has taken the focus
+fn has_open_dialog(window: &ApplicationWindow) -> bool {
+    window.application().is_some_and(|app| {
+        app.windows().iter().any(|other| {
+            other.is_visible() && other.transient_for().as_ref() == Some(window.upcast_ref())
+        })
+    })
 }
 
-// Writes the default configuration to the specified path
-fn write_default_config(path: &PathBuf, config: &Config) {
-    // Serialize the config to a pretty TOML string
-    match toml::to_string_pretty(config) {
-        Ok(toml_str) => {
-            // Ensure the parent directory exists before writing the file
-            if let Some(parent) = path.parent() {
-                if !parent.exists() {
-                    if let Err(e) = fs::create_dir_all(parent) {
-                        eprintln!("Error creating config directory {:?}: {}", parent, e);
-                        return; // Stop if directory creation fails
-                    }
-                }
-            }
-            // Write the TOML string to the file
-            if let Err(e) = fs::write(path, toml_str) {
-                eprintln!("Error writing default config file {:?}: {}", path, e);
-            } else {
-                println!("Created default config at {:?}", path);
-            }
+// Puts text on the clipboard of the default display
+fn copy_to_clipboard(text: &str) {
+    if let Some(display) = gtk::gdk::Display::default() {
+        // Get the default GDK display and its clipboard
+        display.clipboard().set_text(text); // Set the clipboard text
+    }
+}
+
+// Empties every output row and resets its error styling
+fn clear_output_rows(rows: &[OutputRow]) {
+    for output_row in rows {
+        clear_output_row(output_row);
+    }
+}
+
+// Empties a single output row and resets its error styling
+fn clear_output_row(output_row: &OutputRow) {
+    output_row.set_error(false);
+    output_row.row.set_tooltip_text(None);
+    output_row.set_text("");
+    set_row_running(output_row, false);
+}
+
+// Shows or hides the row's spinner
+fn set_row_running(output_row: &OutputRow, running: bool) {
+    output_row.spinner.set_visible(running);
+    output_row.spinner.set_spinning(running);
+}
+
+// Reflects a command's lifecycle event in its output row
+fn show_command_event(output_row: &OutputRow, event: CommandEvent) {
+    match event {
+        CommandEvent::Started => {
+            output_row.row.set_tooltip_text(Some("Running…"));
+            set_row_running(output_row, true);
         }
-        Err(e) => {
-            eprintln!("Error serializing default config: {}", e); // Handle serialization errors
+        CommandEvent::Output(chunk) => output_row.append_text(&chunk),
+        event => {
+            set_row_running(output_row, false);
+            match &event {
+                CommandEvent::Finished(output) => output_row.row.set_tooltip_text(Some(&format!(
+                    "Finished in {} ms",
+                    output.duration.as_millis()
+                ))),
+                _ => output_row.row.set_tooltip_text(None),
+            }
+            // Failures are styled as errors rather than shown like regular output
+            output_row.set_error(event.is_error());
+            output_row.set_text(&event.result_text());
         }
     }
 }
+
+// Removes a debounced run that hasn't fired yet
+fn cancel_pending_run(pending_run: &RefCell<Option<glib::SourceId>>) {
+    if let Some(source_id) = pending_run.borrow_mut().take() {
+        source_id.remove();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hints_are_single_letters_while_there_are_enough() {
+        assert_eq!(hint_labels(3), ["a", "s", "d"]);
+        let labels = hint_labels(HINT_KEYS.len());
+        assert!(labels.iter().all(|label| label.len() == 1));
+        assert_eq!(labels.concat(), HINT_KEYS);
+    }
+
+    #[test]
+    fn hints_are_two_letters_once_there_are_too_many() {
+        let labels = hint_labels(HINT_KEYS.len() + 1);
+        assert_eq!(labels.len(), HINT_KEYS.len() + 1);
+        assert_eq!(&labels[..3], ["aa", "as", "ad"]);
+        assert!(labels.iter().all(|label| label.len() == 2));
+        // No hint is a prefix of another, so typing one never stops at the wrong row
+        let unique: std::collections::BTreeSet<&String> = labels.iter().collect();
+        assert_eq!(unique.len(), labels.len());
+    }
+}
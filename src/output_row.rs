@@ -0,0 +1,319 @@
+// --- Output Rows ---
+// One command's output: monospace, selectable and multi-line, collapsed to its first line until expanded.
+
+use adw::prelude::*;
+use adw::{HeaderBar, PreferencesRow, WindowTitle};
+use gtk::glib;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk::{
+    gdk::Key, Align, Box, Button, EventControllerKey, Image, Label, Orientation, ScrolledWindow,
+    SearchBar, SearchEntry, Spinner, TextSearchFlags, TextView, ToggleButton, WrapMode,
+};
+
+use crate::config::CommandConfig;
+use crate::copy_to_clipboard;
+
+// Widgets making up one command's output row
+pub struct OutputRow {
+    pub row: PreferencesRow,
+    pub spinner: Spinner,  // Shown while the command is still running
+    pub hint_label: Label, // Shows the row's hint while in hint mode
+    output_label: Label,
+    expand_button: ToggleButton, // Shows every line instead of just the first
+    text: Rc<RefCell<String>>, // The whole output, of which the label may only show the first line
+}
+
+impl OutputRow {
+    // Creates the output row for the command at `index`, with its copy button and running spinner
+    pub fn new(index: usize, command: &CommandConfig) -> OutputRow {
+        let row = PreferencesRow::builder()
+            .title(command.label()) // Read out by screen readers
+            .activatable(true)
+            .build();
+        let text = Rc::new(RefCell::new(String::new()));
+
+        // --- Header: hint, icon, label and buttons ---
+        let header = Box::new(Orientation::Horizontal, 6);
+
+        // The row's hint, shown in front of everything else while in hint mode
+        let hint_label = Label::builder()
+            .visible(false)
+            .css_classes(["accent", "heading"])
+            .build();
+        header.append(&hint_label);
+
+        // Show the command's icon, if it has one, in front of the label
+        if let Some(icon) = command.icon() {
+            header.append(&Image::from_icon_name(icon));
+        }
+
+        let title_label = Label::builder()
+            .label(command.label())
+            .xalign(0.0)
+            .hexpand(true)
+            .ellipsize(gtk::pango::EllipsizeMode::End)
+            .css_classes(["caption-heading", "dim-label"])
+            .build();
+        header.append(&title_label);
+
+        // Show a spinner next to the buttons while the command runs
+        let spinner = Spinner::new();
+        spinner.set_visible(false);
+        header.append(&spinner);
+
+        let expand_button = ToggleButton::builder()
+            .icon_name("pan-down-symbolic")
+            .tooltip_text("Show All Lines")
+            .valign(Align::Center)
+            .visible(false) // Only needed once the output has more than one line
+            .css_classes(["flat"])
+            .build();
+        header.append(&expand_button);
+
+        let full_output_button = Button::builder()
+            .icon_name("view-fullscreen-symbolic")
+            .tooltip_text("Show Full Output")
+            .valign(Align::Center)
+            .css_classes(["flat"])
+            .build();
+        header.append(&full_output_button);
+
+        // Create a Copy button for this command's output
+        let copy_button = Button::builder()
+            .icon_name("edit-copy-symbolic")
+            .tooltip_text("Copy Output")
+            .valign(Align::Center)
+            .css_classes(["flat"])
+            .build();
+        header.append(&copy_button);
+
+        // --- Output ---
+        let output_label = Label::builder()
+            .xalign(0.0)
+            .wrap(true)
+            .wrap_mode(gtk::pango::WrapMode::WordChar)
+            .selectable(true)
+            .visible(false) // Hidden while there's no output, so empty rows stay compact
+            .css_classes(["monospace"])
+            .build();
+
+        let content = Box::builder()
+            .orientation(Orientation::Vertical)
+            .spacing(6)
+            .margin_top(8)
+            .margin_bottom(8)
+            .margin_start(12)
+            .margin_end(12)
+            .build();
+        content.append(&header);
+        content.append(&output_label);
+        row.set_child(Some(&content));
+
+        // --- Signals ---
+        let text_clone = Rc::clone(&text);
+        copy_button.connect_clicked(move |_| copy_to_clipboard(&text_clone.borrow()));
+
+        let output_label_clone = output_label.clone();
+        let text_clone = Rc::clone(&text);
+        expand_button.connect_toggled(move |expand_button| {
+            show_text(&output_label_clone, expand_button, &text_clone.borrow());
+        });
+
+        let text_clone = Rc::clone(&text);
+        let title = command.label().to_string();
+        full_output_button.connect_clicked(move |button| {
+            let parent = button.root().and_downcast::<gtk::Window>();
+            show_full_output(parent.as_ref(), &title, &text_clone.borrow());
+        });
+
+        // Enter on the row, or clicking it, copies its output and dismisses the window
+        // The list box activates the row's action, counting from 1 like the shortcuts
+        row.set_action_name(Some("app.copy-result-and-hide"));
+        row.set_action_target_value(Some(&((index + 1) as i32).to_variant()));
+
+        OutputRow {
+            row,
+            spinner,
+            hint_label,
+            output_label,
+            expand_button,
+            text,
+        }
+    }
+
+    // The command's label
+    pub fn title(&self) -> glib::GString {
+        self.row.title()
+    }
+
+    // The whole output, including lines hidden while collapsed
+    pub fn text(&self) -> String {
+        self.text.borrow().clone()
+    }
+
+    // Replaces the output
+    pub fn set_text(&self, text: &str) {
+        *self.text.borrow_mut() = text.to_string();
+        self.show_text();
+    }
+
+    // Adds to the output as it arrives, so progressive commands update live
+    pub fn append_text(&self, chunk: &str) {
+        self.text.borrow_mut().push_str(chunk);
+        self.show_text();
+    }
+
+    // Styles the output as an error rather than regular output
+    pub fn set_error(&self, error: bool) {
+        if error {
+            self.output_label.add_css_class("error");
+        } else {
+            self.output_label.remove_css_class("error");
+        }
+    }
+
+    fn show_text(&self) {
+        show_text(&self.output_label, &self.expand_button, &self.text.borrow());
+    }
+}
+
+// Shows the output in the label: every line when expanded, only the first one otherwise
+fn show_text(output_label: &Label, expand_button: &ToggleButton, text: &str) {
+    let text = text.trim_end();
+    let first_line = text.lines().next().unwrap_or_default();
+    let multi_line = first_line.len() < text.len();
+
+    expand_button.set_visible(multi_line);
+    if multi_line && !expand_button.is_active() {
+        output_label.set_text(&format!("{}…", first_line));
+    } else {
+        output_label.set_text(text);
+    }
+    output_label.set_visible(!text.is_empty());
+}
+
+// Opens the output in a window of its own, with scrolling and search
+fn show_full_output(parent: Option<&gtk::Window>, title: &str, text: &str) {
+    let text_view = TextView::builder()
+        .editable(false)
+        .monospace(true)
+        .wrap_mode(WrapMode::WordChar)
+        .top_margin(12)
+        .bottom_margin(12)
+        .left_margin(12)
+        .right_margin(12)
+        .build();
+    text_view.buffer().set_text(text);
+    let scroll = ScrolledWindow::builder()
+        .child(&text_view)
+        .vexpand(true)
+        .build();
+
+    let search_entry = SearchEntry::builder()
+        .placeholder_text("Search Output")
+        .build();
+    let search_bar = SearchBar::builder().child(&search_entry).build();
+    search_bar.connect_entry(&search_entry);
+
+    let search_button = ToggleButton::builder()
+        .icon_name("system-search-symbolic")
+        .tooltip_text("Search")
+        .build();
+    search_button
+        .bind_property("active", &search_bar, "search-mode-enabled")
+        .bidirectional()
+        .build();
+
+    let header_bar = HeaderBar::builder()
+        .title_widget(&WindowTitle::new(title, "Full Output"))
+        .build();
+    header_bar.pack_end(&search_button);
+
+    let content = Box::new(Orientation::Vertical, 0);
+    content.append(&header_bar);
+    content.append(&search_bar);
+    content.append(&scroll);
+
+    let window = adw::Window::builder()
+        .title(title)
+        .modal(true)
+        .destroy_with_parent(true) // Don't keep the app running once the main window is gone
+        .default_width(600)
+        .default_height(500)
+        .content(&content)
+        .build();
+    window.set_transient_for(parent);
+    // Belonging to the app lets the main window tell it has a dialog open, and not hide on focus loss
+    window.set_application(parent.and_then(|parent| parent.application()).as_ref());
+    search_bar.set_key_capture_widget(Some(&window)); // Typing anywhere starts a search
+
+    // Jump to the first match while typing, and on to the next or previous one from there
+    let text_view_clone = text_view.clone();
+    search_entry.connect_search_changed(move |entry| {
+        let start = text_view_clone.buffer().start_iter();
+        find_match(&text_view_clone, &entry.text(), &start, true);
+    });
+    let text_view_clone = text_view.clone();
+    let find_next = move |entry: &SearchEntry| {
+        let (_, end) = text_view_clone
+            .buffer()
+            .selection_bounds()
+            .unwrap_or_else(|| {
+                let cursor = text_view_clone.buffer().start_iter();
+                (cursor, cursor)
+            });
+        find_match(&text_view_clone, &entry.text(), &end, true);
+    };
+    search_entry.connect_next_match(find_next.clone());
+    search_entry.connect_activate(find_next);
+    let text_view_clone = text_view.clone();
+    search_entry.connect_previous_match(move |entry| {
+        let buffer = text_view_clone.buffer();
+        let (start, _) = buffer
+            .selection_bounds()
+            .unwrap_or_else(|| (buffer.end_iter(), buffer.end_iter()));
+        find_match(&text_view_clone, &entry.text(), &start, false);
+    });
+
+    // Escape closes the window once there's no search to stop
+    let key_controller = EventControllerKey::new();
+    let window_clone = window.clone();
+    let search_bar_clone = search_bar.clone();
+    key_controller.connect_key_pressed(move |_, keyval, _, _| {
+        if keyval != Key::Escape {
+            return glib::Propagation::Proceed;
+        }
+        if search_bar_clone.is_search_mode() {
+            search_bar_clone.set_search_mode(false);
+        } else {
+            window_clone.close();
+        }
+        glib::Propagation::Stop
+    });
+    window.add_controller(key_controller);
+
+    window.present();
+}
+
+// Selects the next match of `query` from `from` in the given direction, wrapping around at the end
+fn find_match(text_view: &TextView, query: &str, from: &gtk::TextIter, forward: bool) {
+    if query.is_empty() {
+        return;
+    }
+    let buffer = text_view.buffer();
+    let flags = TextSearchFlags::CASE_INSENSITIVE | TextSearchFlags::TEXT_ONLY;
+    let found = if forward {
+        from.forward_search(query, flags, None)
+            .or_else(|| buffer.start_iter().forward_search(query, flags, None))
+    } else {
+        from.backward_search(query, flags, None)
+            .or_else(|| buffer.end_iter().backward_search(query, flags, None))
+    };
+    if let Some((mut start, end)) = found {
+        buffer.select_range(&start, &end);
+        text_view.scroll_to_iter(&mut start, 0.1, false, 0.0, 0.0);
+    }
+}
@@ -0,0 +1,618 @@
+// --- Command Execution ---
+// Runs configured commands in the background and reports their lifecycle as structured events.
+// Nothing in here touches GTK, so other front ends can consume the same updates.
+
+use gtk::glib; // For shell-style argument parsing
+
+use std::ffi::OsString;
+use std::io::{self, ErrorKind, Read, Write};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::{CommandConfig, Config};
+
+// How often a running command checks whether its run has been superseded
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Message sent from a command's background thread to whoever is listening
+pub struct CommandUpdate {
+    pub generation: u64,   // The run this update belongs to
+    pub command_id: usize, // Index of the command in the config
+    pub event: CommandEvent,
+}
+
+// Lifecycle of a single command within a run
+pub enum CommandEvent {
+    Started,
+    Output(String), // A chunk of stdout, sent as soon as the command prints it
+    Finished(CommandOutput), // The command ran to completion, successfully or not
+    SpawnFailed(String), // The command line couldn't be parsed or the program started
+    Failed(String), // The command started but we lost track of it
+    TimedOut(u64),  // Killed after this many seconds
+}
+
+impl CommandEvent {
+    // Whether the command ended without succeeding
+    pub fn is_error(&self) -> bool {
+        match self {
+            CommandEvent::Started | CommandEvent::Output(_) => false,
+            CommandEvent::Finished(output) => !output.status.success(),
+            CommandEvent::SpawnFailed(_) | CommandEvent::Failed(_) | CommandEvent::TimedOut(_) => {
+                true
+            }
+        }
+    }
+
+    // The text to show for how the command ended: its output, or why it failed
+    pub fn result_text(&self) -> String {
+        match self {
+            CommandEvent::Started => String::new(),
+            CommandEvent::Output(chunk) => chunk.clone(),
+            // If successful, show the standard output, trimming trailing whitespace
+            CommandEvent::Finished(output) if output.status.success() => {
+                output.stdout.trim_end().to_string()
+            }
+            // If failed, show the status code and standard error
+            CommandEvent::Finished(output) => {
+                format!("Failed ({}):\n{}", output.status, output.stderr.trim_end())
+            }
+            CommandEvent::SpawnFailed(message) | CommandEvent::Failed(message) => message.clone(),
+            CommandEvent::TimedOut(timeout_secs) => format!("Timed out after {}s", timeout_secs),
+        }
+    }
+}
+
+// Everything a finished command produced
+pub struct CommandOutput {
+    pub status: ExitStatus,
+    pub stdout: String,
+    pub stderr: String,
+    pub duration: Duration,
+}
+
+// Hands out run generations; starting a new run makes every older one stale
+#[derive(Clone, Default)]
+pub struct Generations(Arc<AtomicU64>);
+
+impl Generations {
+    // Starts a new run, superseding any run still in flight
+    pub fn next(&self) -> RunToken {
+        let generation = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+        RunToken {
+            generation,
+            current: Arc::clone(&self.0),
+        }
+    }
+
+    // Whether the given generation is still the latest run
+    pub fn is_current(&self, generation: u64) -> bool {
+        self.0.load(Ordering::SeqCst) == generation
+    }
+}
+
+// Identifies one run of the commands so its workers can tell when they have been superseded
+#[derive(Clone)]
+pub struct RunToken {
+    generation: u64,
+    current: Arc<AtomicU64>,
+}
+
+impl RunToken {
+    pub fn is_stale(&self) -> bool {
+        self.current.load(Ordering::SeqCst) != self.generation
+    }
+}
+
+// Runs commands in separate threads and sends updates via channel
+pub fn run_commands_async(
+    input: String,
+    config: Arc<Config>,
+    sender: async_channel::Sender<CommandUpdate>,
+    token: RunToken,
+    on_change: bool, // Skip commands that opted out of running on change
+) {
+    // Iterate over each command defined in the configuration
+    for (command_id, command_config) in config
+        .commands
+        .iter()
+        .enumerate()
+        .filter(|(_, command)| !on_change || command.on_change())
+    {
+        let command = command_config.clone(); // Clone the command config for the thread
+        let input_clone = input.clone(); // Clone the input string for the thread
+        let sender_clone = sender.clone(); // Clone the channel sender for the thread
+        let token_clone = token.clone(); // Clone the run token for the thread
+        let timeout_secs = command.timeout_secs().or(config.timeout_secs); // Per-command timeout wins
+
+        // Spawn a new OS thread to execute the command in the background
+        thread::spawn(move || {
+            // Use send_blocking because we are in a synchronous thread
+            let send = |event| {
+                let update = CommandUpdate {
+                    generation: token_clone.generation,
+                    command_id,
+                    event,
+                };
+                if let Err(e) = sender_clone.send_blocking(update) {
+                    eprintln!("Failed to send command update to main thread: {}", e);
+                }
+            };
+
+            send(CommandEvent::Started);
+            // Execute the command, streaming its output and reporting how it ended,
+            // unless a newer run cancelled it
+            let on_output = &mut |chunk| send(CommandEvent::Output(chunk));
+            if let Some(event) = execute_command(
+                &command,
+                &input_clone,
+                &token_clone,
+                timeout_secs,
+                on_output,
+            ) {
+                send(event);
+            }
+        });
+    }
+}
+
+// Runs the commands on the input, resolving to how each one ended, in config order
+// Commands whose run was superseded, or skipped as they don't run on change, end up as None
+pub async fn run_to_completion(
+    input: String,
+    config: Arc<Config>,
+    token: RunToken,
+    on_change: bool, // Skip commands that opted out of running on change
+) -> Vec<Option<CommandEvent>> {
+    let (sender, receiver) = async_channel::unbounded();
+    // The sender is dropped once every command has finished, which closes the channel
+    run_commands_async(input, Arc::clone(&config), sender, token, on_change);
+
+    let mut events: Vec<Option<CommandEvent>> = config.commands.iter().map(|_| None).collect();
+    while let Ok(update) = receiver.recv().await {
+        match update.event {
+            CommandEvent::Started | CommandEvent::Output(_) => {}
+            event => events[update.command_id] = Some(event),
+        }
+    }
+    events
+}
+
+// Builds the argv for a command: POSIX shell words by default, or `/bin/sh -c` when opted in
+fn command_argv(command: &CommandConfig) -> Result<Vec<OsString>, String> {
+    if command.shell() {
+        // Hand the whole command line to the shell so pipes, redirections and $VARS work
+        return Ok(vec!["/bin/sh".into(), "-c".into(), command.cmd().into()]);
+    }
+
+    // Split the command line the way a POSIX shell would, honouring quotes and escapes
+    glib::shell_parse_argv(command.cmd())
+        .map_err(|e| format!("Error parsing command '{}': {}", command.cmd(), e))
+}
+
+// Executes a single command, writes input to its stdin, and captures stdout/stderr
+// Stdout is passed to `on_output` chunk by chunk while the command is still running
+// Returns the event ending the command, or None if the run was superseded and the command killed
+pub fn execute_command(
+    command: &CommandConfig,
+    input: &str,
+    token: &RunToken,
+    timeout_secs: Option<u64>,
+    on_output: &mut dyn FnMut(String),
+) -> Option<CommandEvent> {
+    let argv = match command_argv(command) {
+        Ok(argv) => argv,
+        Err(e) => return Some(CommandEvent::SpawnFailed(e)),
+    };
+
+    let program = &argv[0]; // The first part is the program name
+    let args = &argv[1..]; // The rest are arguments
+
+    let mut process = Command::new(program);
+    process
+        .args(args) // Pass the arguments
+        .stdin(Stdio::piped()) // Pipe stdin so we can write to it
+        .stdout(Stdio::piped()) // Pipe stdout to capture output
+        .stderr(Stdio::piped()) // Pipe stderr to capture errors
+        .process_group(0); // Lead a new process group so the whole tree can be killed
+    if let Some(env) = command.env() {
+        process.envs(env); // Add the command's own environment variables
+    }
+    if let Some(cwd) = command.cwd() {
+        process.current_dir(cwd); // Run in the command's working directory
+    }
+
+    // Attempt to spawn the command
+    let started = Instant::now();
+    let mut child = match process.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            // Show the parsed argv to help debug quoting
+            return Some(CommandEvent::SpawnFailed(format!(
+                "Failed to execute {:?}: {}",
+                argv, e
+            )));
+        }
+    };
+
+    // Read stdout and stderr on their own threads so we can keep watching for cancellation
+    // The channel disconnects once both pipes have been closed
+    let (chunk_sender, chunks) = mpsc::channel();
+    if let Some(stdout) = child.stdout.take() {
+        spawn_pipe_streamer(stdout, Pipe::Stdout, chunk_sender.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        spawn_pipe_streamer(stderr, Pipe::Stderr, chunk_sender);
+    }
+    let mut stdout = StreamedOutput::default();
+    let mut stderr = Vec::new();
+
+    // Feed the input while the output is being drained, so a command that fills its stdout
+    // before reading all of its stdin can't deadlock us
+    let stdin_errors = child
+        .stdin
+        .take()
+        .map(|stdin| spawn_stdin_writer(stdin, input.to_string()));
+
+    // Wait for the command to finish, killing it if a newer run supersedes it or it times out
+    // Processes it left running in the background can hold the pipes open long after it exits,
+    // so the same checks apply until both pipes have been closed
+    let deadline = timeout_secs.map(|secs| started + Duration::from_secs(secs));
+    let mut pipes_open = true;
+    loop {
+        if token.is_stale() {
+            kill_process_group(&mut child);
+            return None;
+        }
+        if let (Some(deadline), Some(secs)) = (deadline, timeout_secs) {
+            if Instant::now() >= deadline {
+                kill_process_group(&mut child);
+                return Some(CommandEvent::TimedOut(secs));
+            }
+        }
+
+        // Wait a little for output, then take whatever else has arrived meanwhile
+        if pipes_open {
+            match chunks.recv_timeout(POLL_INTERVAL) {
+                Ok(chunk) => {
+                    let received = std::iter::once(chunk).chain(chunks.try_iter());
+                    receive_chunks(received, &mut stdout, &mut stderr, on_output);
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => pipes_open = false,
+            }
+        } else {
+            thread::sleep(POLL_INTERVAL); // The command closed its pipes but is still running
+        }
+
+        match has_exited(&child) {
+            Ok(true) if !pipes_open => break,
+            Ok(_) => {}
+            Err(e) => {
+                // Handle error waiting for output
+                kill_process_group(&mut child);
+                return Some(CommandEvent::Failed(format!(
+                    "Failed to get command output: {}",
+                    e
+                )));
+            }
+        }
+    }
+
+    // Only now reap the child, which has already exited
+    let status = match child.wait() {
+        Ok(status) => status,
+        Err(e) => {
+            return Some(CommandEvent::Failed(format!(
+                "Failed to get command output: {}",
+                e
+            )))
+        }
+    };
+
+    // A command that was only fed part of its input didn't really succeed
+    // The writer is normally done by now; one still blocked on a background process is left behind
+    if let Some(Ok(e)) = stdin_errors.map(|errors| errors.recv_timeout(POLL_INTERVAL)) {
+        return Some(CommandEvent::Failed(format!(
+            "Error writing to stdin: {}",
+            e
+        )));
+    }
+
+    Some(CommandEvent::Finished(CommandOutput {
+        status,
+        stdout: String::from_utf8_lossy(&stdout.bytes).into_owned(),
+        stderr: String::from_utf8_lossy(&stderr).into_owned(),
+        duration: started.elapsed(),
+    }))
+}
+
+// Sorts received chunks into stdout, which is streamed, and stderr, which is only collected
+fn receive_chunks(
+    chunks: impl Iterator<Item = (Pipe, Vec<u8>)>,
+    stdout: &mut StreamedOutput,
+    stderr: &mut Vec<u8>,
+    on_output: &mut dyn FnMut(String),
+) {
+    let mut stdout_chunks = Vec::new();
+    for (pipe, chunk) in chunks {
+        match pipe {
+            Pipe::Stdout => stdout_chunks.push(chunk),
+            Pipe::Stderr => stderr.extend_from_slice(&chunk),
+        }
+    }
+    stdout.receive(stdout_chunks.into_iter(), on_output);
+}
+
+// Kills the child along with everything it spawned, then reaps it
+// Must only be called before the child has been reaped, i.e. while it's running or a zombie
+fn kill_process_group(child: &mut Child) {
+    // The child leads its own process group, so a negative pid signals the whole group
+    // SAFETY: kill() has no memory safety requirements. The child hasn't been reaped yet, so its
+    // pid is still taken and the process group it leads can't be another one that reused the id
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    let _ = child.wait(); // Reap the killed child
+}
+
+// Whether the child has exited, leaving it unreaped so its process group can still be killed
+fn has_exited(child: &Child) -> io::Result<bool> {
+    // SAFETY: siginfo_t is plain data, for which all zeroes is a valid value
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    // SAFETY: `info` is valid for writes, and WNOWAIT leaves the child to be reaped by Child::wait
+    let result = unsafe {
+        libc::waitid(
+            libc::P_PID,
+            child.id() as libc::id_t,
+            &mut info,
+            libc::WEXITED | libc::WNOHANG | libc::WNOWAIT,
+        )
+    };
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    // With WNOHANG, si_pid is left at zero while the child is still running
+    // SAFETY: waitid() filled in `info` for a child state change, where si_pid is set
+    Ok(unsafe { info.si_pid() } != 0)
+}
+
+// Writes the input to a child's stdin on a background thread, then closes it to signal the end
+// The returned channel receives the error if writing failed, and disconnects once the writer is done
+fn spawn_stdin_writer<W: Write + Send + 'static>(
+    mut stdin: W,
+    input: String,
+) -> mpsc::Receiver<io::Error> {
+    let (sender, errors) = mpsc::channel();
+    thread::spawn(move || match stdin.write_all(input.as_bytes()) {
+        Ok(()) => {}
+        // The command exited or closed stdin without reading all of it, which is its call
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => {}
+        Err(e) => {
+            let _ = sender.send(e); // Nobody is listening if the command was killed meanwhile
+        }
+    });
+    errors
+}
+
+// Which of a child's output pipes a chunk was read from
+#[derive(Clone, Copy)]
+enum Pipe {
+    Stdout,
+    Stderr,
+}
+
+// Reads a child's pipe on a background thread, sending each chunk as soon as it arrives
+fn spawn_pipe_streamer<R: Read + Send + 'static>(
+    mut pipe: R,
+    name: Pipe,
+    sender: mpsc::Sender<(Pipe, Vec<u8>)>,
+) {
+    thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        // Stop at end of file, on a read error, or once nobody is listening anymore
+        while let Ok(read) = pipe.read(&mut buffer) {
+            if read == 0 || sender.send((name, buffer[..read].to_vec())).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+// Stdout collected so far, plus any trailing bytes of a character split across chunks
+#[derive(Default)]
+struct StreamedOutput {
+    bytes: Vec<u8>,
+    pending: Vec<u8>,
+}
+
+impl StreamedOutput {
+    // Collects received chunks and passes on the text they complete
+    fn receive(
+        &mut self,
+        chunks: impl Iterator<Item = Vec<u8>>,
+        on_output: &mut dyn FnMut(String),
+    ) {
+        for chunk in chunks {
+            self.bytes.extend_from_slice(&chunk);
+            self.pending.extend_from_slice(&chunk);
+        }
+
+        // Hold back an incomplete UTF-8 sequence at the end until the rest of it arrives
+        let complete = match std::str::from_utf8(&self.pending) {
+            Ok(_) => self.pending.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => self.pending.len(), // Genuinely invalid bytes are decoded lossily
+        };
+        if complete > 0 {
+            let rest = self.pending.split_off(complete);
+            on_output(String::from_utf8_lossy(&self.pending).into_owned());
+            self.pending = rest;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeMap;
+    use std::fs;
+
+    use crate::config::CommandTable;
+
+    // A command run through `/bin/sh -c`
+    fn shell(cmd: &str) -> CommandConfig {
+        CommandConfig::Detailed(CommandTable {
+            cmd: cmd.to_string(),
+            name: None,
+            label: None,
+            icon: None,
+            shell: true,
+            on_change: true,
+            timeout_secs: None,
+            env: BTreeMap::new(),
+            cwd: None,
+        })
+    }
+
+    // Runs a command to its end in a run of its own
+    fn execute(
+        command: &CommandConfig,
+        input: &str,
+        timeout_secs: Option<u64>,
+    ) -> Option<CommandEvent> {
+        execute_command(
+            command,
+            input,
+            &Generations::default().next(),
+            timeout_secs,
+            &mut |_| {},
+        )
+    }
+
+    // The stdout of a command that finished successfully
+    fn stdout(event: Option<CommandEvent>) -> String {
+        match event {
+            Some(CommandEvent::Finished(output)) if output.status.success() => output.stdout,
+            Some(event) => panic!("command didn't succeed: {}", event.result_text()),
+            None => panic!("command was cancelled"),
+        }
+    }
+
+    #[test]
+    fn timeout_kills_the_whole_process_group() {
+        let marker = std::env::temp_dir().join(format!("putput-timeout-{}", std::process::id()));
+        let _ = fs::remove_file(&marker);
+        // The background job outlives the shell unless the whole group is killed
+        let command = shell(&format!(
+            "(sleep 2; touch '{}') & sleep 5; echo x",
+            marker.display()
+        ));
+
+        let started = Instant::now();
+        let event = execute(&command, "", Some(1));
+        assert!(matches!(event, Some(CommandEvent::TimedOut(1))));
+        assert!(started.elapsed() < Duration::from_secs(3));
+
+        thread::sleep(Duration::from_millis(2500));
+        assert!(
+            !marker.exists(),
+            "a process of the group survived the timeout"
+        );
+    }
+
+    #[test]
+    fn stale_token_cancels_the_run() {
+        let generations = Generations::default();
+        let token = generations.next();
+        let superseding = generations.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            superseding.next();
+        });
+
+        let started = Instant::now();
+        let event = execute_command(&shell("sleep 5"), "", &token, None, &mut |_| {});
+        assert!(event.is_none());
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn large_input_does_not_deadlock() {
+        let input = "0123456789abcdef\n".repeat(500_000); // About 8 MB, far more than a pipe holds
+        let output = stdout(execute(
+            &CommandConfig::Plain("cat".into()),
+            &input,
+            Some(30),
+        ));
+        assert_eq!(output.len(), input.len());
+    }
+
+    #[test]
+    fn command_exiting_before_reading_its_input_succeeds() {
+        let input = "x".repeat(1_000_000);
+        let output = stdout(execute(
+            &CommandConfig::Plain("head -c1".into()),
+            &input,
+            Some(30),
+        ));
+        assert_eq!(output, "x");
+    }
+
+    #[test]
+    fn background_descendant_holding_the_pipes_times_out() {
+        let started = Instant::now();
+        let event = execute(&shell("sleep 600 & echo hi"), "", Some(1));
+        assert!(matches!(event, Some(CommandEvent::TimedOut(1))));
+        assert!(started.elapsed() < Duration::from_secs(3));
+    }
+
+    #[test]
+    fn background_descendant_holding_the_pipes_is_cancelled() {
+        let generations = Generations::default();
+        let token = generations.next();
+        let superseding = generations.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(300));
+            superseding.next();
+        });
+
+        let started = Instant::now();
+        let event = execute_command(&shell("sleep 600 & echo hi"), "", &token, None, &mut |_| {});
+        assert!(event.is_none());
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn streamed_output_holds_back_split_characters() {
+        let mut streamed = StreamedOutput::default();
+        let mut received = Vec::new();
+        let on_output = &mut |chunk| received.push(chunk);
+
+        // "é" is 0xC3 0xA9 in UTF-8, split here across two chunks
+        streamed.receive(vec![b"a\xC3".to_vec()].into_iter(), on_output);
+        streamed.receive(vec![b"\xA9b".to_vec()].into_iter(), on_output);
+
+        assert_eq!(received, ["a", "éb"]);
+        assert_eq!(streamed.bytes, "aéb".as_bytes());
+    }
+
+    #[test]
+    fn command_argv_honours_quotes() {
+        let command = CommandConfig::Plain(r#"jq '.a b' "c d" e\ f"#.into());
+        assert_eq!(
+            command_argv(&command).unwrap(),
+            ["jq", ".a b", "c d", "e f"].map(OsString::from)
+        );
+        assert_eq!(
+            command_argv(&shell("tr a-z A-Z | rev")).unwrap(),
+            ["/bin/sh", "-c", "tr a-z A-Z | rev"].map(OsString::from)
+        );
+        assert!(command_argv(&CommandConfig::Plain("echo 'unterminated".into())).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+// --- GNOME Shell Search Provider ---
+// Runs the commands on what's typed in the Activities overview and shows each output as a result.
+
+use adw::prelude::*;
+use adw::Application;
+use gtk::{gio, glib};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::runner::{run_to_completion, CommandEvent, Generations};
+use crate::service::current_config;
+use crate::{copy_to_clipboard, Ui};
+
+const INTERFACE_NAME: &str = "org.gnome.Shell.SearchProvider2";
+
+const INTERFACE_XML: &str = r#"
+<node>
+  <interface name="org.gnome.Shell.SearchProvider2">
+    <method name="GetInitialResultSet">
+      <arg type="as" name="terms" direction="in"/>
+      <arg type="as" name="results" direction="out"/>
+    </method>
+    <method name="GetSubsearchResultSet">
+      <arg type="as" name="previous_results" direction="in"/>
+      <arg type="as" name="terms" direction="in"/>
+      <arg type="as" name="results" direction="out"/>
+    </method>
+    <method name="GetResultMetas">
+      <arg type="as" name="identifiers" direction="in"/>
+      <arg type="aa{sv}" name="metas" direction="out"/>
+    </method>
+    <method name="ActivateResult">
+      <arg type="s" name="identifier" direction="in"/>
+      <arg type="as" name="terms" direction="in"/>
+      <arg type="u" name="timestamp" direction="in"/>
+    </method>
+    <method name="LaunchSearch">
+      <arg type="as" name="terms" direction="in"/>
+      <arg type="u" name="timestamp" direction="in"/>
+    </method>
+  </interface>
+</node>
+"#;
+
+// A method call from the shell, with its arguments
+enum Method {
+    Search(Vec<String>),         // Both the initial search and narrowing it down
+    GetResultMetas(Vec<String>), // How to show the given results
+    ActivateResult(String),      // A result was clicked
+    LaunchSearch(Vec<String>),   // The app's icon was clicked, to continue the search in the window
+}
+
+impl gio::prelude::DBusMethodCall for Method {
+    fn parse_call(
+        _object_path: &str,
+        _interface: Option<&str>,
+        method: &str,
+        params: glib::Variant,
+    ) -> Result<Self, glib::Error> {
+        let call = match method {
+            "GetInitialResultSet" => params
+                .get::<(Vec<String>,)>()
+                .map(|(terms,)| Method::Search(terms)),
+            "GetSubsearchResultSet" => params
+                .get::<(Vec<String>, Vec<String>)>()
+                .map(|(_, terms)| Method::Search(terms)),
+            "GetResultMetas" => params
+                .get::<(Vec<String>,)>()
+                .map(|(identifiers,)| Method::GetResultMetas(identifiers)),
+            "ActivateResult" => params
+                .get::<(String, Vec<String>, u32)>()
+                .map(|(identifier, _, _)| Method::ActivateResult(identifier)),
+            "LaunchSearch" => params
+                .get::<(Vec<String>, u32)>()
+                .map(|(terms, _)| Method::LaunchSearch(terms)),
+            _ => {
+                return Err(glib::Error::new(
+                    gio::DBusError::UnknownMethod,
+                    &format!("No such method: {}", method),
+                ))
+            }
+        };
+        call.ok_or_else(|| {
+            glib::Error::new(
+                gio::DBusError::InvalidArgs,
+                &format!("Invalid arguments for {}", method),
+            )
+        })
+    }
+}
+
+// A command's output from the latest search
+struct SearchResult {
+    label: String,
+    icon: Option<String>,
+    output: String,
+}
+
+// State kept between the shell's calls for one search
+#[derive(Default)]
+struct SearchProvider {
+    results: RefCell<Vec<SearchResult>>, // Results of the latest search; the index is the result id
+    generations: Generations,            // Each keystroke supersedes the previous search
+}
+
+// Exports the search provider on the application's object path
+// Searches use the window while there is one, and the config file itself otherwise
+pub fn register(app: &Application, config_path: PathBuf, ui_slot: &Rc<RefCell<Option<Rc<Ui>>>>) {
+    let (Some(connection), Some(object_path)) = (app.dbus_connection(), app.dbus_object_path())
+    else {
+        return; // Not on the session bus, so the shell can't reach us
+    };
+    let interface_info = match gio::DBusNodeInfo::for_xml(INTERFACE_XML)
+        .map(|node_info| node_info.lookup_interface(INTERFACE_NAME))
+    {
+        Ok(Some(interface_info)) => interface_info,
+        Ok(None) => return,
+        Err(e) => {
+            eprintln!("Error parsing search provider interface: {}", e);
+            return;
+        }
+    };
+
+    let app = app.clone();
+    let ui_slot = Rc::clone(ui_slot);
+    let provider = Rc::new(SearchProvider::default());
+    let registration = connection
+        .register_object(&object_path, &interface_info)
+        .typed_method_call::<Method>()
+        .invoke_and_return_future_local(move |_, _, call| {
+            let hold = app.hold(); // Don't quit for inactivity in the middle of a search
+            let provider = Rc::clone(&provider);
+            let app = app.clone();
+            let ui_slot = Rc::clone(&ui_slot);
+            let config_path = config_path.clone();
+            async move {
+                let result = handle_call(&provider, &app, &config_path, &ui_slot, call).await;
+                drop(hold);
+                result
+            }
+        })
+        .build();
+    if let Err(e) = registration {
+        eprintln!("Error exporting search provider: {}", e);
+    }
+}
+
+// Answers a call from the shell using the window's selected profile, or without a window,
+// the config file and the profile that was last selected with it
+async fn handle_call(
+    provider: &SearchProvider,
+    app: &Application,
+    config_path: &Path,
+    ui_slot: &RefCell<Option<Rc<Ui>>>,
+    call: Method,
+) -> Result<Option<glib::Variant>, glib::Error> {
+    match call {
+        Method::Search(terms) => {
+            let ui = ui_slot.borrow().clone();
+            let config = current_config(config_path, ui.as_deref())?;
+            Ok(Some(
+                provider.search(config, terms.join(" ")).await.to_variant(),
+            ))
+        }
+        Method::GetResultMetas(identifiers) => {
+            Ok(Some(provider.result_metas(&identifiers).to_variant()))
+        }
+        Method::ActivateResult(identifier) => {
+            // Copy the result, like Ctrl+N does in the window
+            let results = provider.results.borrow();
+            if let Some(result) = identifier.parse().ok().and_then(|i: usize| results.get(i)) {
+                copy_to_clipboard(&result.output);
+            }
+            Ok(None)
+        }
+        Method::LaunchSearch(terms) => {
+            if ui_slot.borrow().is_none() {
+                app.activate(); // Opens the window
+            }
+            let ui = ui_slot.borrow().clone();
+            if let Some(ui) = ui {
+                ui.set_input(&terms.join(" "));
+                ui.present();
+                ui.run_commands();
+            }
+            Ok(None)
+        }
+    }
+}
+
+impl SearchProvider {
+    // Runs the commands on the query, returning the ids of the commands that succeeded with output
+    // Every keystroke searches again, so this runs only what runs on change, the way the window would
+    async fn search(&self, config: Arc<Config>, query: String) -> Vec<String> {
+        let token = self.generations.next(); // Supersedes the search for the previous keystroke
+        if query.chars().count() < config.run_on_change_min_length {
+            self.results.replace(Vec::new());
+            return Vec::new();
+        }
+
+        // Wait for typing to pause before running anything
+        glib::timeout_future(Duration::from_millis(config.run_on_change_debounce_ms)).await;
+        if token.is_stale() {
+            return Vec::new(); // The shell has moved on to a newer query
+        }
+
+        let events = run_to_completion(query, Arc::clone(&config), token.clone(), true).await;
+        if token.is_stale() {
+            return Vec::new(); // The shell has moved on to a newer query
+        }
+
+        let results: Vec<SearchResult> = config
+            .commands
+            .iter()
+            .zip(events)
+            .map(|(command, event)| SearchResult {
+                label: command.label().to_string(),
+                icon: command.icon().map(str::to_string),
+                // Failed commands are left out rather than shown as results
+                output: match event {
+                    Some(event @ CommandEvent::Finished(_)) if !event.is_error() => {
+                        event.result_text()
+                    }
+                    _ => String::new(),
+                },
+            })
+            .collect();
+        let ids = results
+            .iter()
+            .enumerate()
+            .filter(|(_, result)| !result.output.is_empty())
+            .map(|(id, _)| id.to_string())
+            .collect();
+        self.results.replace(results);
+        ids
+    }
+
+    // The output as the result's name and the command's label below it
+    fn result_metas(&self, identifiers: &[String]) -> Vec<HashMap<String, glib::Variant>> {
+        let results = self.results.borrow();
+        identifiers
+            .iter()
+            .filter_map(|identifier| {
+                let result = results.get(identifier.parse::<usize>().ok()?)?;
+                let mut meta = HashMap::new();
+                meta.insert("id".to_string(), identifier.to_variant());
+                meta.insert("name".to_string(), result.output.to_variant());
+                meta.insert("description".to_string(), result.label.to_variant());
+                if let Some(icon) = &result.icon {
+                    meta.insert("gicon".to_string(), icon.to_variant());
+                }
+                Some(meta)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,209 @@
+// --- D-Bus Service ---
+// Lets other programs run inputs through the configured commands over the session bus.
+
+use adw::prelude::*;
+use adw::Application;
+use gtk::{gio, glib};
+
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::config::{load_config, Config};
+use crate::runner::{run_to_completion, CommandEvent, Generations};
+use crate::state::{load_last_profile, save_last_profile};
+use crate::{Ui, APP_ID};
+
+// How long a service started without a window waits for another call before quitting
+const SERVICE_TIMEOUT_MS: u32 = 10_000;
+
+// The interface exported next to the application's own, named after the app
+const INTERFACE_XML: &str = r#"
+<node>
+  <interface name="com.github.bjesus.putput">
+    <method name="Run">
+      <arg type="s" name="input" direction="in"/>
+      <arg type="a(sis)" name="results" direction="out"/>
+    </method>
+    <method name="ListCommands">
+      <arg type="a(ss)" name="commands" direction="out"/>
+    </method>
+    <method name="SetProfile">
+      <arg type="s" name="name" direction="in"/>
+    </method>
+  </interface>
+</node>
+"#;
+
+// A method call on the interface, with its arguments
+enum Method {
+    Run(String), // Runs the commands on the input, returning (label, exit status, output) for each
+    ListCommands, // Returns (label, command line) for each command
+    SetProfile(String), // Switches to a profile; an empty name is the top-level commands
+}
+
+impl gio::prelude::DBusMethodCall for Method {
+    fn parse_call(
+        _object_path: &str,
+        _interface: Option<&str>,
+        method: &str,
+        params: glib::Variant,
+    ) -> Result<Self, glib::Error> {
+        let call = match method {
+            "Run" => params.get::<(String,)>().map(|(input,)| Method::Run(input)),
+            "ListCommands" => Some(Method::ListCommands),
+            "SetProfile" => params
+                .get::<(String,)>()
+                .map(|(name,)| Method::SetProfile(name)),
+            _ => {
+                return Err(glib::Error::new(
+                    gio::DBusError::UnknownMethod,
+                    &format!("No such method: {}", method),
+                ))
+            }
+        };
+        call.ok_or_else(|| {
+            glib::Error::new(
+                gio::DBusError::InvalidArgs,
+                &format!("Invalid arguments for {}", method),
+            )
+        })
+    }
+}
+
+// Exports the interface on the application's object path; only the primary instance does this
+// Calls use the window while there is one, and the config file itself otherwise
+pub fn register(app: &Application, config_path: PathBuf, ui_slot: &Rc<RefCell<Option<Rc<Ui>>>>) {
+    let (Some(connection), Some(object_path)) = (app.dbus_connection(), app.dbus_object_path())
+    else {
+        return; // Not on the session bus, so there's nothing to export
+    };
+    let interface_info = match gio::DBusNodeInfo::for_xml(INTERFACE_XML)
+        .map(|node_info| node_info.lookup_interface(APP_ID))
+    {
+        Ok(Some(interface_info)) => interface_info,
+        Ok(None) => return,
+        Err(e) => {
+            eprintln!("Error parsing D-Bus interface: {}", e);
+            return;
+        }
+    };
+
+    // Started for D-Bus activation without a window, so stay around a while between calls
+    if app.flags().contains(gio::ApplicationFlags::IS_SERVICE) {
+        app.set_inactivity_timeout(SERVICE_TIMEOUT_MS);
+    }
+
+    let app = app.clone();
+    let ui_slot = Rc::clone(ui_slot);
+    let registration = connection
+        .register_object(&object_path, &interface_info)
+        .typed_method_call::<Method>()
+        .invoke_and_return_future_local(move |_, _, call| {
+            let hold = app.hold(); // Don't quit for inactivity in the middle of a call
+            let ui = ui_slot.borrow().clone();
+            let config_path = config_path.clone();
+            async move {
+                let result = handle_call(&config_path, ui, call).await;
+                drop(hold);
+                result
+            }
+        })
+        .build();
+    if let Err(e) = registration {
+        eprintln!("Error exporting D-Bus interface: {}", e);
+    }
+}
+
+// Answers a method call using the window's selected profile, or without a window,
+// the config file and the profile that was last selected with it
+async fn handle_call(
+    config_path: &Path,
+    ui: Option<Rc<Ui>>,
+    call: Method,
+) -> Result<Option<glib::Variant>, glib::Error> {
+    match call {
+        Method::Run(input) => {
+            let config = current_config(config_path, ui.as_deref())?;
+            Ok(Some(run(input, config).await.to_variant()))
+        }
+        Method::ListCommands => {
+            let commands: Vec<(String, String)> = current_config(config_path, ui.as_deref())?
+                .commands
+                .iter()
+                .map(|command| (command.label().to_string(), command.cmd().to_string()))
+                .collect();
+            Ok(Some(commands.to_variant()))
+        }
+        Method::SetProfile(name) => {
+            let profile = (!name.is_empty()).then_some(name);
+            let exists = |config: &Config| {
+                profile
+                    .as_ref()
+                    .is_none_or(|name| config.profiles.contains_key(name))
+            };
+            let exists = match &ui {
+                Some(ui) => exists(&ui.loaded_config.borrow()),
+                None => exists(&load_config_file(config_path)?),
+            };
+            if !exists {
+                return Err(glib::Error::new(
+                    gio::DBusError::InvalidArgs,
+                    &format!("No such profile: {}", profile.unwrap_or_default()),
+                ));
+            }
+            match ui {
+                Some(ui) => ui.select_profile(profile),
+                // The window picks it up once it's opened
+                None => save_last_profile(config_path, profile.as_deref()),
+            }
+            Ok(None)
+        }
+    }
+}
+
+// The config the commands run with: the window's, or the last used profile of the config file
+pub fn current_config(config_path: &Path, ui: Option<&Ui>) -> Result<Arc<Config>, glib::Error> {
+    if let Some(ui) = ui {
+        return Ok(Arc::clone(&ui.config.borrow()));
+    }
+    let config = load_config_file(config_path)?;
+    let profile = load_last_profile(config_path);
+    Ok(Arc::new(config.for_profile(profile.as_deref())))
+}
+
+// Loads the config file, reporting a broken one to the caller
+fn load_config_file(config_path: &Path) -> Result<Config, glib::Error> {
+    load_config(config_path).map_err(|e| {
+        glib::Error::new(
+            gio::DBusError::Failed,
+            &format!("Couldn't load config: {}", e),
+        )
+    })
+}
+
+// Runs every command on the input, separately from the window's own runs
+// Commands that didn't exit by themselves get -1 as their exit status
+async fn run(input: String, config: Arc<Config>) -> Vec<(String, i32, String)> {
+    let events = run_to_completion(
+        input,
+        Arc::clone(&config),
+        Generations::default().next(),
+        false,
+    )
+    .await;
+    config
+        .commands
+        .iter()
+        .zip(events)
+        .map(|(command, event)| {
+            let status = match &event {
+                Some(CommandEvent::Finished(output)) => output.status.code().unwrap_or(-1),
+                _ => -1,
+            };
+            let output = event.map_or_else(|| "No result".to_string(), |event| event.result_text());
+            (command.label().to_string(), status, output)
+        })
+        .collect()
+}
@@ -0,0 +1,64 @@
+// --- Remembered State ---
+// Things putput remembers between launches, kept apart from the user's hand-written config.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct State {
+    #[serde(default)]
+    last_profiles: BTreeMap<String, String>, // Config file path -> last selected profile
+}
+
+// Determines the state file path, under the XDG state directory where there is one
+fn get_state_path() -> PathBuf {
+    dirs::state_dir()
+        .or_else(dirs::cache_dir)
+        .unwrap_or_else(|| PathBuf::from(".")) // Fallback to current directory if neither is found
+        .join("putput")
+        .join("state.toml")
+}
+
+// Reads the state file, treating a missing or broken one as empty
+fn load_state() -> State {
+    fs::read_to_string(get_state_path())
+        .ok()
+        .and_then(|content| toml::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+// Writes the state file, creating its directory if needed
+fn save_state(state: &State) {
+    let state_path = get_state_path();
+    if let Some(parent) = state_path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    match toml::to_string_pretty(state) {
+        Ok(toml_str) => {
+            if let Err(e) = fs::write(&state_path, toml_str) {
+                eprintln!("Error writing state file {:?}: {}", state_path, e);
+            }
+        }
+        Err(e) => eprintln!("Error serializing state: {}", e),
+    }
+}
+
+// The profile that was selected when the given config was last used
+pub fn load_last_profile(config_path: &Path) -> Option<String> {
+    load_state()
+        .last_profiles
+        .remove(config_path.to_string_lossy().as_ref())
+}
+
+// Remembers the selected profile for the given config; None is the top-level commands
+pub fn save_last_profile(config_path: &Path, profile: Option<&str>) {
+    let mut state = load_state();
+    let key = config_path.to_string_lossy().into_owned();
+    match profile {
+        Some(profile) => state.last_profiles.insert(key, profile.to_string()),
+        None => state.last_profiles.remove(&key),
+    };
+    save_state(&state);
+}